@@ -1,5 +1,9 @@
+use std::collections::HashMap;
+use std::env;
 use std::fs;
 
+use regex::Regex;
+
 struct PasswordData {
     min: usize,
     max: usize,
@@ -15,8 +19,8 @@ impl PasswordData {
         let ch = token.pop()?.chars().next()?;
         let minmax: Vec<&str> = token.pop()?.split('-').collect();
 
-        let min_fromstr = minmax[0].parse().ok()?;
-        let max_fromstr = minmax[1].parse().ok()?;
+        let min_fromstr = minmax.first()?.parse().ok()?;
+        let max_fromstr = minmax.get(1)?.parse().ok()?;
 
         Some(PasswordData {
             min: min_fromstr,
@@ -25,28 +29,236 @@ impl PasswordData {
             pw,
         })
     }
+
+    // 1-based position lookup, out-of-range positions are reported instead of panicking
+    fn char_at(&self, pos: usize) -> Result<char, Violation> {
+        pos.checked_sub(1)
+            .and_then(|i| self.pw.chars().nth(i))
+            .ok_or_else(|| {
+                Violation::Error(format!(
+                    "position {} is out of range for password of length {}",
+                    pos,
+                    self.pw.chars().count()
+                ))
+            })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Violation {
+    // the password does not satisfy the policy
+    Failed(String),
+    // the policy could not be evaluated for this entry
+    Error(String),
+}
+
+trait PasswordPolicy {
+    fn name(&self) -> String;
+    fn check(&self, entry: &PasswordData) -> Result<(), Violation>;
+}
+
+// part 1: the policy letter must occur between min and max times
+struct CountInRange;
+
+impl PasswordPolicy for CountInRange {
+    fn name(&self) -> String {
+        String::from("count-in-range")
+    }
+
+    fn check(&self, entry: &PasswordData) -> Result<(), Violation> {
+        let count = entry.pw.chars().filter(|&c| c == entry.ch).count();
+        if (entry.min..=entry.max).contains(&count) {
+            Ok(())
+        } else {
+            Err(Violation::Failed(format!(
+                "'{}' occurs {} times, expected {} to {}",
+                entry.ch, count, entry.min, entry.max
+            )))
+        }
+    }
+}
+
+// part 2: exactly one of the two (1-based) positions must hold the policy letter
+struct PositionXor;
+
+impl PasswordPolicy for PositionXor {
+    fn name(&self) -> String {
+        String::from("position-xor")
+    }
+
+    fn check(&self, entry: &PasswordData) -> Result<(), Violation> {
+        let first = entry.char_at(entry.min)? == entry.ch;
+        let second = entry.char_at(entry.max)? == entry.ch;
+        if first ^ second {
+            Ok(())
+        } else {
+            Err(Violation::Failed(format!(
+                "'{}' found at {} of positions {} and {}",
+                entry.ch,
+                if first { "both" } else { "neither" },
+                entry.min,
+                entry.max
+            )))
+        }
+    }
+}
+
+struct MatchesRegex(Regex);
+
+impl PasswordPolicy for MatchesRegex {
+    fn name(&self) -> String {
+        format!("regex {}", self.0.as_str())
+    }
+
+    fn check(&self, entry: &PasswordData) -> Result<(), Violation> {
+        if self.0.is_match(&entry.pw) {
+            Ok(())
+        } else {
+            Err(Violation::Failed(format!(
+                "does not match /{}/",
+                self.0.as_str()
+            )))
+        }
+    }
+}
+
+// Shannon entropy of the password's characters in bits per character
+struct MinEntropy(f64);
+
+fn entropy(pw: &str) -> f64 {
+    let mut freq: HashMap<char, usize> = HashMap::new();
+    for c in pw.chars() {
+        *freq.entry(c).or_insert(0) += 1;
+    }
+    let len = pw.chars().count() as f64;
+
+    freq.values()
+        .map(|&n| {
+            let p = n as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+impl PasswordPolicy for MinEntropy {
+    fn name(&self) -> String {
+        format!("min-entropy {}", self.0)
+    }
+
+    fn check(&self, entry: &PasswordData) -> Result<(), Violation> {
+        let bits = entropy(&entry.pw);
+        if bits >= self.0 {
+            Ok(())
+        } else {
+            Err(Violation::Failed(format!(
+                "entropy of {:.3} bits per char is below {}",
+                bits, self.0
+            )))
+        }
+    }
+}
+
+// no character may directly follow itself
+struct NoRepeats;
+
+impl PasswordPolicy for NoRepeats {
+    fn name(&self) -> String {
+        String::from("no-repeats")
+    }
+
+    fn check(&self, entry: &PasswordData) -> Result<(), Violation> {
+        let chars: Vec<char> = entry.pw.chars().collect();
+        match chars.windows(2).position(|w| w[0] == w[1]) {
+            None => Ok(()),
+            Some(i) => Err(Violation::Failed(format!(
+                "'{}' repeats at position {}",
+                chars[i],
+                i + 2
+            ))),
+        }
+    }
+}
+
+// Policy files list one policy per line, optionally followed by its argument:
+//
+//   # comment
+//   count-in-range
+//   position-xor
+//   regex ^[a-z]+$
+//   min-entropy 2.5
+//   no-repeats
+fn parse_policies(input: &str) -> Result<Vec<Box<dyn PasswordPolicy>>, String> {
+    let mut policies: Vec<Box<dyn PasswordPolicy>> = vec![];
+
+    for (i, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut token = line.splitn(2, ' ');
+        let name = token.next().unwrap_or_default();
+        let arg = token.next().map(str::trim);
+
+        let policy: Box<dyn PasswordPolicy> = match (name, arg) {
+            ("count-in-range", None) => Box::new(CountInRange),
+            ("position-xor", None) => Box::new(PositionXor),
+            ("no-repeats", None) => Box::new(NoRepeats),
+            ("regex", Some(re)) => Box::new(MatchesRegex(
+                Regex::new(re).map_err(|e| format!("line {}: {}", i + 1, e))?,
+            )),
+            ("min-entropy", Some(bits)) => {
+                Box::new(MinEntropy(bits.parse().map_err(|_| {
+                    format!("line {}: invalid entropy '{}'", i + 1, bits)
+                })?))
+            }
+            _ => return Err(format!("line {}: unknown policy '{}'", i + 1, line)),
+        };
+        policies.push(policy);
+    }
+
+    Ok(policies)
+}
+
+#[derive(Debug, PartialEq)]
+struct Failure {
+    entry: usize,
+    policy: String,
+    violation: Violation,
+}
+
+fn report(pw_data: &[PasswordData], policies: &[Box<dyn PasswordPolicy>]) -> Vec<Failure> {
+    let mut failures = vec![];
+
+    for (i, pdata) in pw_data.iter().enumerate() {
+        for policy in policies {
+            if let Err(violation) = policy.check(pdata) {
+                failures.push(Failure {
+                    entry: i,
+                    policy: policy.name(),
+                    violation,
+                });
+            }
+        }
+    }
+    failures
+}
+
+fn count_valid(pw_data: &[PasswordData], policy: &dyn PasswordPolicy) -> usize {
+    pw_data
+        .iter()
+        .filter(|pdata| policy.check(pdata).is_ok())
+        .count()
 }
 
 struct Solution;
 impl Solution {
     fn part1(pw_data: &[PasswordData]) -> usize {
-        pw_data
-            .iter()
-            .filter(|pdata| {
-                let count = pdata.pw.chars().filter(|&c| c == pdata.ch).count();
-                count >= pdata.min && count <= pdata.max
-            })
-            .count()
+        count_valid(pw_data, &CountInRange)
     }
 
     fn part2(pw_data: &[PasswordData]) -> usize {
-        pw_data
-            .iter()
-            .filter(|pdata| {
-                (pdata.pw.chars().nth(pdata.min - 1).unwrap() == pdata.ch)
-                    ^ (pdata.pw.chars().nth(pdata.max - 1).unwrap() == pdata.ch)
-            })
-            .count()
+        count_valid(pw_data, &PositionXor)
     }
 }
 
@@ -58,6 +270,76 @@ fn main() {
         .map(|l| PasswordData::parse_input(l).unwrap())
         .collect();
 
-    println!("p1: {}", Solution::part1(&pw_data));
-    println!("p2: {}", Solution::part2(&pw_data));
+    match env::args().nth(1) {
+        Some(policy_file) => {
+            let policies =
+                parse_policies(&fs::read_to_string(&policy_file).expect("Policy file not found!"))
+                    .unwrap_or_else(|e| panic!("invalid policy file: {}", e));
+
+            let failures = report(&pw_data, &policies);
+            for f in failures.iter() {
+                let (kind, reason) = match &f.violation {
+                    Violation::Failed(reason) => ("fails", reason),
+                    Violation::Error(reason) => ("cannot check", reason),
+                };
+                println!("line {}: {} {}: {}", f.entry + 1, kind, f.policy, reason);
+            }
+            println!("{} violations in {} entries", failures.len(), pw_data.len());
+        }
+        None => {
+            println!("p1: {}", Solution::part1(&pw_data));
+            println!("p2: {}", Solution::part2(&pw_data));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Vec<PasswordData> {
+        "1-3 a: abcde\n1-3 b: cdefg\n2-9 c: ccccccccc"
+            .lines()
+            .map(|l| PasswordData::parse_input(l).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_day02() {
+        let pw_data = example();
+        assert_eq!(Solution::part1(&pw_data), 2);
+        assert_eq!(Solution::part2(&pw_data), 1);
+    }
+
+    #[test]
+    fn test_day02_out_of_range_position() {
+        let pdata = PasswordData::parse_input("2-12 a: abc").unwrap();
+        assert_eq!(
+            PositionXor.check(&pdata),
+            Err(Violation::Error(String::from(
+                "position 12 is out of range for password of length 3"
+            )))
+        );
+    }
+
+    #[test]
+    fn test_day02_report() {
+        let policies =
+            parse_policies("# demo\nno-repeats\nregex ^[a-e]+$\nmin-entropy 1.5").unwrap();
+        let failures = report(&example(), &policies);
+
+        let summary: Vec<(usize, &str)> = failures
+            .iter()
+            .map(|f| (f.entry, f.policy.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, "regex ^[a-e]+$"),
+                (2, "no-repeats"),
+                (2, "min-entropy 1.5")
+            ]
+        );
+        assert!(parse_policies("max-length 3").is_err());
+    }
 }
@@ -1,88 +1,666 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fmt;
 use std::fs;
+use std::num::ParseIntError;
+use std::str::FromStr;
 
-type Passport = HashMap<String, String>;
+use regex::Regex;
+use serde::Deserialize;
 
-fn parse_batch(batch: &str) -> Passport {
-    let mut pp = Passport::new();
-    let fields: Vec<String> = batch.split_whitespace().map(str::to_owned).collect();
+type Fields = HashMap<String, String>;
 
-    for f in fields.iter() {
-        let mut tmp = f.split(':');
-        let k = tmp.next().unwrap().to_owned().clone();
-        let v = tmp.next().unwrap().to_owned().clone();
-        pp.insert(k, v);
+fn parse_batch(batch: &str) -> Result<Fields, String> {
+    let mut fields = Fields::new();
+
+    for token in batch.split_whitespace() {
+        let (k, v) = match token.find(':') {
+            Some(i) if i > 0 => (&token[..i], &token[i + 1..]),
+            _ => return Err(format!("malformed token '{}'", token)),
+        };
+        if fields.insert(k.to_owned(), v.to_owned()).is_some() {
+            return Err(format!("duplicate field '{}'", k));
+        }
     }
-    pp
+    Ok(fields)
 }
 
-fn passport_valid_p1(passport: &Passport) -> bool {
-    let len = passport.len();
-    (7 <= len || len <= 8)
-        && ["byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid"]
-            .iter()
-            .all(|k| passport.contains_key(*k))
+// Parses `s` into `T` only if formatting the result gives back `s` again, so that
+// converting a passport back to batch text never alters a value.
+fn parse_exact<T: FromStr + fmt::Display>(s: &str) -> Option<T> {
+    s.parse().ok().filter(|t: &T| t.to_string() == s)
+}
+
+macro_rules! year_newtype {
+    ($name:ident) => {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct $name(u32);
+
+        impl FromStr for $name {
+            type Err = ParseIntError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                s.parse().map($name)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+    };
+}
+
+year_newtype!(BirthYear);
+year_newtype!(IssueYear);
+year_newtype!(ExpirationYear);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HeightUnit {
+    Cm,
+    In,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Height {
+    value: u32,
+    unit: HeightUnit,
+}
+
+impl FromStr for Height {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (value, unit) = s.split_at(split);
+
+        let unit = match unit {
+            "cm" => HeightUnit::Cm,
+            "in" => HeightUnit::In,
+            _ => return Err(format!("unknown height unit '{}'", unit)),
+        };
+        let value = value
+            .parse()
+            .map_err(|_| format!("invalid height '{}'", s))?;
+
+        Ok(Height { value, unit })
+    }
+}
+
+impl fmt::Display for Height {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let unit = match self.unit {
+            HeightUnit::Cm => "cm",
+            HeightUnit::In => "in",
+        };
+        write!(f, "{}{}", self.value, unit)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct HairColor {
+    r: u8,
+    g: u8,
+    b: u8,
+}
+
+impl FromStr for HairColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+            .ok_or_else(|| format!("invalid hair color '{}'", s))?;
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+
+        Ok(HairColor {
+            r: channel(0),
+            g: channel(2),
+            b: channel(4),
+        })
+    }
+}
+
+impl fmt::Display for HairColor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EyeColor {
+    Amber,
+    Blue,
+    Brown,
+    Gray,
+    Green,
+    Hazel,
+    Other,
+}
+
+impl FromStr for EyeColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "amb" => Ok(EyeColor::Amber),
+            "blu" => Ok(EyeColor::Blue),
+            "brn" => Ok(EyeColor::Brown),
+            "gry" => Ok(EyeColor::Gray),
+            "grn" => Ok(EyeColor::Green),
+            "hzl" => Ok(EyeColor::Hazel),
+            "oth" => Ok(EyeColor::Other),
+            _ => Err(format!("unknown eye color '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for EyeColor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            EyeColor::Amber => "amb",
+            EyeColor::Blue => "blu",
+            EyeColor::Brown => "brn",
+            EyeColor::Gray => "gry",
+            EyeColor::Green => "grn",
+            EyeColor::Hazel => "hzl",
+            EyeColor::Other => "oth",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// digits only, kept as text to preserve leading zeros
+#[derive(Debug, Clone, PartialEq)]
+struct PassportId(String);
+
+impl FromStr for PassportId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.is_empty() && s.chars().all(|c| c.is_ascii_digit()) {
+            Ok(PassportId(s.to_owned()))
+        } else {
+            Err(format!("invalid passport id '{}'", s))
+        }
+    }
+}
+
+impl fmt::Display for PassportId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Passport {
+    birth_year: Option<BirthYear>,
+    issue_year: Option<IssueYear>,
+    expiration_year: Option<ExpirationYear>,
+    height: Option<Height>,
+    hair_color: Option<HairColor>,
+    eye_color: Option<EyeColor>,
+    passport_id: Option<PassportId>,
+    country_id: Option<String>,
+    // unknown fields and known fields whose value does not fit their type, sorted by key
+    untyped: Vec<(String, String)>,
+}
+
+impl Passport {
+    fn fields(&self) -> Fields {
+        fn insert<T: ToString>(fields: &mut Fields, key: &str, value: &Option<T>) {
+            if let Some(v) = value {
+                fields.insert(key.to_owned(), v.to_string());
+            }
+        }
+
+        let mut fields: Fields = self.untyped.iter().cloned().collect();
+        insert(&mut fields, "byr", &self.birth_year);
+        insert(&mut fields, "iyr", &self.issue_year);
+        insert(&mut fields, "eyr", &self.expiration_year);
+        insert(&mut fields, "hgt", &self.height);
+        insert(&mut fields, "hcl", &self.hair_color);
+        insert(&mut fields, "ecl", &self.eye_color);
+        insert(&mut fields, "pid", &self.passport_id);
+        insert(&mut fields, "cid", &self.country_id);
+        fields
+    }
+}
+
+impl FromStr for Passport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut pp = Passport::default();
+        let mut fields: Vec<(String, String)> = parse_batch(s)?.into_iter().collect();
+        fields.sort();
+
+        for (k, v) in fields {
+            let typed = match k.as_str() {
+                "byr" => parse_exact(&v).map(|t| pp.birth_year = Some(t)),
+                "iyr" => parse_exact(&v).map(|t| pp.issue_year = Some(t)),
+                "eyr" => parse_exact(&v).map(|t| pp.expiration_year = Some(t)),
+                "hgt" => parse_exact(&v).map(|t| pp.height = Some(t)),
+                "hcl" => parse_exact(&v).map(|t| pp.hair_color = Some(t)),
+                "ecl" => parse_exact(&v).map(|t| pp.eye_color = Some(t)),
+                "pid" => parse_exact(&v).map(|t| pp.passport_id = Some(t)),
+                "cid" => {
+                    pp.country_id = Some(v.clone());
+                    Some(())
+                }
+                _ => None,
+            };
+            if typed.is_none() {
+                pp.untyped.push((k, v));
+            }
+        }
+        Ok(pp)
+    }
 }
 
-fn year_valid(year: &str, min: u32, max: u32) -> bool {
-    (min..=max).contains(&year.parse::<u32>().unwrap_or_default())
+// Writes the passport back as a single line of batch text
+impl fmt::Display for Passport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut fields: Vec<(String, String)> = self.fields().into_iter().collect();
+        fields.sort();
+
+        let tokens: Vec<String> = fields.iter().map(|(k, v)| format!("{}:{}", k, v)).collect();
+        write!(f, "{}", tokens.join(" "))
+    }
 }
 
-fn hgt_valid(height: &str) -> bool {
-    let (val, unit) = height.split_at(height.len() - 2);
-    matches!(
-        (val.parse::<u32>(), unit),
-        (Ok(150..=193), "cm") | (Ok(59..=76), "in")
-    )
+// Validation rules for a single passport field. All given constraints are checked
+// against the raw value; `min`/`max` apply to "int" fields, `units` maps each
+// allowed unit of a "measure" field to its inclusive value range.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FieldRule {
+    #[serde(default)]
+    required: bool,
+    #[serde(default, rename = "type")]
+    kind: FieldType,
+    min: Option<i64>,
+    max: Option<i64>,
+    #[serde(default)]
+    units: BTreeMap<String, (i64, i64)>,
+    pattern: Option<String>,
+    #[serde(rename = "enum")]
+    one_of: Option<Vec<String>>,
+    #[serde(skip)]
+    regex: Option<Regex>,
 }
 
-fn hcl_valid(hcl: &str) -> bool {
-    hcl.len() == 7 && hcl.starts_with('#') && hcl.chars().skip(1).all(|c| c.is_ascii_hexdigit())
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum FieldType {
+    #[default]
+    String,
+    Int,
+    Measure,
 }
 
-fn ecl_valid(ecl: &str) -> bool {
-    ["amb", "blu", "brn", "gry", "grn", "hzl", "oth"].contains(&ecl)
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Schema {
+    #[serde(default)]
+    allow_unknown: bool,
+    fields: BTreeMap<String, FieldRule>,
 }
 
-fn pid_valid(pid: &str) -> bool {
-    pid.len() == 9 && pid.chars().all(|c| c.is_ascii_digit())
+#[derive(Debug, PartialEq)]
+struct Violation {
+    field: String,
+    reason: String,
 }
 
-fn passport_valid_p2(passport: &Passport) -> bool {
-    if !passport_valid_p1(passport) {
-        return false;
+impl Violation {
+    fn new(field: &str, reason: String) -> Self {
+        Self {
+            field: field.to_owned(),
+            reason,
+        }
     }
+}
+
+const DEFAULT_SCHEMA: &str = r#"
+allow_unknown = false
+
+[fields.byr]
+required = true
+type = "int"
+min = 1920
+max = 2002
+
+[fields.iyr]
+required = true
+type = "int"
+min = 2010
+max = 2020
+
+[fields.eyr]
+required = true
+type = "int"
+min = 2020
+max = 2030
+
+[fields.hgt]
+required = true
+type = "measure"
+units = { cm = [150, 193], in = [59, 76] }
+
+[fields.hcl]
+required = true
+pattern = "^#[0-9a-f]{6}$"
+
+[fields.ecl]
+required = true
+enum = ["amb", "blu", "brn", "gry", "grn", "hzl", "oth"]
+
+[fields.pid]
+required = true
+pattern = "^[0-9]{9}$"
+
+[fields.cid]
+required = false
+"#;
+
+impl Schema {
+    fn from_toml(s: &str) -> Result<Self, String> {
+        let mut schema: Schema = toml::from_str(s).map_err(|e| e.to_string())?;
 
-    passport.iter().all(|(k, v)| match k.as_str() {
-        "byr" => year_valid(v, 1920, 2002),
-        "iyr" => year_valid(v, 2010, 2020),
-        "eyr" => year_valid(v, 2020, 2030),
-        "hgt" => hgt_valid(v),
-        "hcl" => hcl_valid(v),
-        "ecl" => ecl_valid(v),
-        "pid" => pid_valid(v),
-        "cid" => true,
-        _ => false,
-    })
+        for (name, rule) in schema.fields.iter_mut() {
+            if let Some(pattern) = &rule.pattern {
+                rule.regex =
+                    Some(Regex::new(pattern).map_err(|e| format!("field '{}': {}", name, e))?);
+            }
+            if rule.kind == FieldType::Measure && rule.units.is_empty() {
+                return Err(format!("field '{}': measure without units", name));
+            }
+        }
+        Ok(schema)
+    }
+
+    fn missing_fields(&self, passport: &Fields) -> Vec<Violation> {
+        self.fields
+            .iter()
+            .filter(|(name, rule)| rule.required && !passport.contains_key(*name))
+            .map(|(name, _)| Violation::new(name, String::from("missing required field")))
+            .collect()
+    }
+
+    fn validate(&self, passport: &Fields) -> Vec<Violation> {
+        let mut violations = self.missing_fields(passport);
+
+        let mut keys: Vec<&String> = passport.keys().collect();
+        keys.sort();
+
+        for key in keys {
+            match self.fields.get(key) {
+                Some(rule) => violations.extend(
+                    rule.check(&passport[key])
+                        .into_iter()
+                        .map(|reason| Violation::new(key, reason)),
+                ),
+                None if !self.allow_unknown => {
+                    violations.push(Violation::new(key, String::from("unknown field")))
+                }
+                None => (),
+            }
+        }
+        violations
+    }
+}
+
+impl FieldRule {
+    // Returns the reasons why `value` violates this rule
+    fn check(&self, value: &str) -> Vec<String> {
+        let mut reasons = vec![];
+
+        match self.kind {
+            FieldType::String => (),
+            FieldType::Int => match value.parse::<i64>() {
+                Ok(n) => reasons.extend(self.check_range(n, self.min, self.max, "")),
+                Err(_) => reasons.push(format!("'{}' is not an integer", value)),
+            },
+            FieldType::Measure => {
+                let split = value
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(value.len());
+                let (num, unit) = value.split_at(split);
+
+                match (num.parse::<i64>(), self.units.get(unit)) {
+                    (Err(_), _) => reasons.push(format!("'{}' has no numeric value", value)),
+                    (_, None) => reasons.push(format!(
+                        "unit '{}' is not one of {:?}",
+                        unit,
+                        self.units.keys().collect::<Vec<_>>()
+                    )),
+                    (Ok(n), Some(&(min, max))) => {
+                        reasons.extend(self.check_range(n, Some(min), Some(max), unit))
+                    }
+                }
+            }
+        }
+
+        if let Some(re) = &self.regex {
+            if !re.is_match(value) {
+                reasons.push(format!("'{}' does not match /{}/", value, re.as_str()));
+            }
+        }
+
+        if let Some(one_of) = &self.one_of {
+            if !one_of.iter().any(|v| v == value) {
+                reasons.push(format!("'{}' is not one of {:?}", value, one_of));
+            }
+        }
+
+        reasons
+    }
+
+    fn check_range(
+        &self,
+        n: i64,
+        min: Option<i64>,
+        max: Option<i64>,
+        unit: &str,
+    ) -> Option<String> {
+        let too_small = min.is_some_and(|min| n < min);
+        let too_large = max.is_some_and(|max| n > max);
+
+        if too_small || too_large {
+            Some(format!(
+                "{}{} is outside {}..={}",
+                n,
+                unit,
+                min.map_or(String::new(), |v| v.to_string()),
+                max.map_or(String::new(), |v| v.to_string())
+            ))
+        } else {
+            None
+        }
+    }
 }
 
 struct Solution;
 
 impl Solution {
-    fn part1(passports: &[Passport]) -> usize {
-        passports.iter().filter(|pp| passport_valid_p1(pp)).count()
+    fn part1(schema: &Schema, passports: &[Passport]) -> usize {
+        passports
+            .iter()
+            .filter(|pp| schema.missing_fields(&pp.fields()).is_empty())
+            .count()
     }
 
-    fn part2(passports: &[Passport]) -> usize {
-        passports.iter().filter(|pp| passport_valid_p2(pp)).count()
+    fn part2(schema: &Schema, passports: &[Passport]) -> usize {
+        passports
+            .iter()
+            .filter(|pp| schema.validate(&pp.fields()).is_empty())
+            .count()
     }
 }
 
 fn main() {
     let input = fs::read_to_string("./input/day04.txt").expect("File not found!");
-    let batch: Vec<&str> = input.split("\n\n").collect();
-    let passports: Vec<Passport> = batch.into_iter().map(parse_batch).collect();
+    let batches: Vec<&str> = input.split("\n\n").collect();
+    // passports numbered by batch, malformed batches are reported and left out
+    let numbered: Vec<(usize, Passport)> = batches
+        .iter()
+        .enumerate()
+        .filter_map(|(i, b)| match b.parse() {
+            Ok(pp) => Some((i + 1, pp)),
+            Err(e) => {
+                eprintln!("passport {}: {}", i + 1, e);
+                None
+            }
+        })
+        .collect();
+    if numbered.len() < batches.len() {
+        println!(
+            "skipped {} of {} passports that could not be parsed",
+            batches.len() - numbered.len(),
+            batches.len()
+        );
+    }
+    let passports: Vec<Passport> = numbered.iter().map(|(_, pp)| pp.clone()).collect();
+
+    let schema = match env::args().nth(1) {
+        Some(schema_file) => {
+            let schema = Schema::from_toml(
+                &fs::read_to_string(&schema_file).expect("Schema file not found!"),
+            )
+            .unwrap_or_else(|e| panic!("invalid schema: {}", e));
+
+            for (n, pp) in &numbered {
+                for v in schema.validate(&pp.fields()) {
+                    println!("passport {}: {}: {}", n, v.field, v.reason);
+                }
+            }
+            schema
+        }
+        None => Schema::from_toml(DEFAULT_SCHEMA).unwrap(),
+    };
+
+    println!("p1: {}", Solution::part1(&schema, &passports));
+    println!("p2: {}", Solution::part2(&schema, &passports));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_day04() {
+        let input = "\
+eyr:1972 cid:100
+hcl:#18171d ecl:amb hgt:170 pid:186cm iyr:2018 byr:1926
 
-    println!("p1: {}", Solution::part1(&passports));
-    println!("p2: {}", Solution::part2(&passports));
+iyr:2019
+hcl:#602927 eyr:1967 hgt:170cm
+ecl:grn pid:012533040 byr:1946
+
+pid:087499704 hgt:74in ecl:grn iyr:2012 eyr:2030 byr:1980
+hcl:#623a2f
+
+eyr:2029 ecl:blu cid:129 byr:1989
+iyr:2014 pid:896056539 hcl:#a97842 hgt:165cm
+
+hcl:#888785
+hgt:164cm byr:2001 iyr:2015 cid:88
+pid:545766238 ecl:hzl
+eyr:2022";
+        let passports: Vec<Passport> = input.split("\n\n").map(|b| b.parse().unwrap()).collect();
+        let schema = Schema::from_toml(DEFAULT_SCHEMA).unwrap();
+
+        assert_eq!(Solution::part1(&schema, &passports), 5);
+        assert_eq!(Solution::part2(&schema, &passports), 3);
+        assert_eq!(
+            schema.validate(&passports[0].fields()),
+            vec![
+                Violation::new("eyr", String::from("1972 is outside 2020..=2030")),
+                Violation::new(
+                    "hgt",
+                    String::from("unit '' is not one of [\"cm\", \"in\"]")
+                ),
+                Violation::new("pid", String::from("'186cm' does not match /^[0-9]{9}$/")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_day04_custom_schema() {
+        let schema = Schema::from_toml(
+            r#"
+allow_unknown = true
+
+[fields.byr]
+required = true
+type = "int"
+min = 1900
+
+[fields.hgt]
+type = "measure"
+units = { m = [1, 2] }
+"#,
+        )
+        .unwrap();
+
+        assert!(schema
+            .validate(&parse_batch("byr:1950 xyz:1 hgt:2m").unwrap())
+            .is_empty());
+        assert_eq!(
+            schema.validate(&parse_batch("byr:18 hgt:x").unwrap()),
+            vec![
+                Violation::new("byr", String::from("18 is outside 1900..=")),
+                Violation::new("hgt", String::from("'x' has no numeric value")),
+            ]
+        );
+        assert!(Schema::from_toml("[fields.x]\ntype = \"float\"").is_err());
+    }
+
+    #[test]
+    fn test_day04_typed_passport() {
+        let pp: Passport =
+            "hcl:#623a2f iyr:2012 pid:087499704 hgt:74in ecl:grn\nxyz:q byr:0980 eyr:2030"
+                .parse()
+                .unwrap();
+
+        assert_eq!(pp.issue_year, Some(IssueYear(2012)));
+        assert_eq!(
+            pp.height,
+            Some(Height {
+                value: 74,
+                unit: HeightUnit::In
+            })
+        );
+        assert_eq!(
+            pp.hair_color,
+            Some(HairColor {
+                r: 0x62,
+                g: 0x3a,
+                b: 0x2f
+            })
+        );
+        assert_eq!(pp.eye_color, Some(EyeColor::Green));
+        assert_eq!(pp.passport_id, Some(PassportId(String::from("087499704"))));
+        assert_eq!(pp.birth_year, None);
+        assert_eq!(
+            pp.untyped,
+            vec![
+                (String::from("byr"), String::from("0980")),
+                (String::from("xyz"), String::from("q"))
+            ]
+        );
+        assert_eq!(pp.to_string().parse::<Passport>(), Ok(pp));
+
+        assert_eq!(
+            "byr:1980 hgt".parse::<Passport>(),
+            Err(String::from("malformed token 'hgt'"))
+        );
+        assert_eq!(
+            ":5".parse::<Passport>(),
+            Err(String::from("malformed token ':5'"))
+        );
+    }
 }
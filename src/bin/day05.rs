@@ -1,37 +1,262 @@
+use std::env;
+use std::fmt;
 use std::fs;
+use std::str::FromStr;
 
-fn parse(input: &str) -> u32 {
-    input
-        .chars()
-        .map(|c| match c {
-            'F' | 'L' => 0,
-            'B' | 'R' => 1,
-            _ => panic!("Invalid input!"),
+// Number of bits used to encode the row and the column of a seat
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Layout {
+    row_bits: u32,
+    col_bits: u32,
+}
+
+impl Layout {
+    const DEFAULT: Layout = Layout {
+        row_bits: 7,
+        col_bits: 3,
+    };
+
+    // keeps seat IDs and the seat tables indexed by them small
+    const MAX_BITS: u32 = 24;
+
+    fn new(row_bits: u32, col_bits: u32) -> Result<Self, String> {
+        if row_bits
+            .checked_add(col_bits)
+            .is_none_or(|bits| bits > Self::MAX_BITS)
+        {
+            return Err(format!(
+                "layout {}x{} has more than {} bits",
+                row_bits,
+                col_bits,
+                Self::MAX_BITS
+            ));
+        }
+        Ok(Layout { row_bits, col_bits })
+    }
+
+    fn rows(&self) -> u32 {
+        1 << self.row_bits
+    }
+
+    fn cols(&self) -> u32 {
+        1 << self.col_bits
+    }
+
+    fn num_seats(&self) -> u32 {
+        self.rows() * self.cols()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BoardingPass {
+    layout: Layout,
+    row: u32,
+    column: u32,
+}
+
+impl BoardingPass {
+    fn from_seat(layout: Layout, row: u32, column: u32) -> Result<Self, String> {
+        if row >= layout.rows() || column >= layout.cols() {
+            return Err(format!(
+                "seat ({}, {}) outside of {}x{} plane",
+                row,
+                column,
+                layout.rows(),
+                layout.cols()
+            ));
+        }
+        Ok(BoardingPass {
+            layout,
+            row,
+            column,
         })
-        .fold(0, |a, b| a << 1 | b)
+    }
+
+    fn from_id(layout: Layout, id: u32) -> Result<Self, String> {
+        Self::from_seat(layout, id >> layout.col_bits, id & (layout.cols() - 1))
+    }
+
+    fn from_code(layout: Layout, code: &str) -> Result<Self, String> {
+        fn decode(part: &str, zero: char, one: char) -> Result<u32, String> {
+            part.chars().try_fold(0, |acc, c| match c {
+                c if c == zero => Ok(acc << 1),
+                c if c == one => Ok(acc << 1 | 1),
+                _ => Err(format!(
+                    "invalid character '{}', expected {} or {}",
+                    c, zero, one
+                )),
+            })
+        }
+
+        let len = (layout.row_bits + layout.col_bits) as usize;
+        if code.len() != len || !code.is_ascii() {
+            return Err(format!("code '{}' must have {} characters", code, len));
+        }
+
+        let (row, column) = code.split_at(layout.row_bits as usize);
+        Self::from_seat(layout, decode(row, 'F', 'B')?, decode(column, 'L', 'R')?)
+    }
+
+    fn id(&self) -> u32 {
+        self.row << self.layout.col_bits | self.column
+    }
+
+    fn code(&self) -> String {
+        fn encode(value: u32, bits: u32, zero: char, one: char) -> String {
+            (0..bits)
+                .rev()
+                .map(|i| if value >> i & 1 == 1 { one } else { zero })
+                .collect()
+        }
+
+        encode(self.row, self.layout.row_bits, 'F', 'B')
+            + &encode(self.column, self.layout.col_bits, 'L', 'R')
+    }
+}
+
+// <row bits>x<column bits>, e.g. 7x3
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bits = |b: &str| b.parse().map_err(|_| format!("invalid layout '{}'", s));
+        match s.split_once('x') {
+            Some((rows, cols)) => Layout::new(bits(rows)?, bits(cols)?),
+            None => Err(format!("layout '{}' must be <row bits>x<column bits>", s)),
+        }
+    }
+}
+
+impl fmt::Display for BoardingPass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+// Marks every seat of the plane as occupied ('#'), free ('.') or missing ('-'). Seats
+// in front of the first and behind the last occupied seat don't exist on this plane.
+fn seat_map(layout: Layout, passes: &[BoardingPass]) -> String {
+    let mut occupied = vec![false; layout.num_seats() as usize];
+    for pass in passes {
+        occupied[pass.id() as usize] = true;
+    }
+    let first = occupied.iter().position(|&o| o).unwrap_or(occupied.len());
+    let last = occupied.iter().rposition(|&o| o).unwrap_or(0);
+
+    let mut map = String::new();
+    for row in 0..layout.rows() {
+        map += &format!("{:>4} ", row);
+        for column in 0..layout.cols() {
+            if column == layout.cols() / 2 {
+                map.push(' ');
+            }
+            let id = (row << layout.col_bits | column) as usize;
+            map.push(match occupied[id] {
+                true => '#',
+                false if (first..=last).contains(&id) => '.',
+                false => '-',
+            });
+        }
+        map.push('\n');
+    }
+    map
 }
 
 struct Solution;
 
 impl Solution {
-    fn part1(seat_ids: &[u32]) -> u32 {
-        *seat_ids.iter().max().unwrap()
+    fn part1(passes: &[BoardingPass]) -> u32 {
+        passes.iter().map(BoardingPass::id).max().unwrap()
     }
 
-    fn part2(seat_ids: &mut Vec<u32>) -> Option<u32> {
-        seat_ids.sort_unstable();
+    fn part2(layout: Layout, passes: &[BoardingPass]) -> Option<BoardingPass> {
+        let mut occupied = vec![false; layout.num_seats() as usize];
+        for pass in passes {
+            occupied[pass.id() as usize] = true;
+        }
 
-        seat_ids.windows(2).find(|w| w[0] + 1 != w[1]).map(|w| w[0] + 1)
+        occupied
+            .windows(3)
+            .position(|w| w[0] && !w[1] && w[2])
+            .and_then(|i| BoardingPass::from_id(layout, i as u32 + 1).ok())
     }
 }
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    // usage: day05 [--map] [--layout <row bits>x<column bits>]
+    let layout = match args.iter().position(|a| a == "--layout") {
+        Some(i) => args
+            .get(i + 1)
+            .expect("missing layout")
+            .parse()
+            .unwrap_or_else(|e| panic!("{}", e)),
+        None => Layout::DEFAULT,
+    };
+
     let input = fs::read_to_string("./input/day05.txt").expect("File not found!");
-    let mut seat_ids: Vec<u32> = input.trim().lines().map(parse).collect();
+    let passes: Vec<BoardingPass> = input
+        .trim()
+        .lines()
+        .map(|l| BoardingPass::from_code(layout, l).unwrap_or_else(|e| panic!("{}", e)))
+        .collect();
+
+    if args.iter().any(|a| a == "--map") {
+        print!("{}", seat_map(layout, &passes));
+    }
 
-    println!("p1: {}", Solution::part1(&seat_ids));
+    println!("p1: {}", Solution::part1(&passes));
+    let seat = Solution::part2(layout, &passes).expect("Empty seat not found!");
     println!(
-        "p2: {}",
-        Solution::part2(&mut seat_ids).expect("Empty seat not found!")
+        "p2: {} ({}, row {}, column {})",
+        seat.id(),
+        seat,
+        seat.row,
+        seat.column
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_day05() {
+        let layout = Layout::DEFAULT;
+        let pass = BoardingPass::from_code(layout, "FBFBBFFRLR").unwrap();
+        assert_eq!((pass.row, pass.column, pass.id()), (44, 5, 357));
+        assert_eq!(BoardingPass::from_id(layout, 357), Ok(pass));
+        assert_eq!(pass.to_string(), "FBFBBFFRLR");
+
+        assert_eq!(
+            BoardingPass::from_code(layout, "BBFFBBFRLL").unwrap().id(),
+            820
+        );
+        assert!(BoardingPass::from_code(layout, "BBFFBBFRLX").is_err());
+        assert!(BoardingPass::from_code(layout, "BBFFBBFRL").is_err());
+        assert!(BoardingPass::from_seat(layout, 128, 0).is_err());
+
+        assert_eq!("7x3".parse(), Ok(Layout::DEFAULT));
+        assert!("16x16".parse::<Layout>().is_err());
+        assert!("32x0".parse::<Layout>().is_err());
+        assert!("7".parse::<Layout>().is_err());
+        assert!("4294967295x1".parse::<Layout>().is_err());
+    }
+
+    #[test]
+    fn test_day05_seat_map() {
+        let layout = Layout::new(2, 2).unwrap();
+        let passes: Vec<BoardingPass> = [5, 6, 8, 10]
+            .iter()
+            .map(|&id| BoardingPass::from_id(layout, id).unwrap())
+            .collect();
+
+        assert_eq!(passes[3].code(), "BFRL");
+        assert_eq!(Solution::part2(layout, &passes).map(|p| p.id()), Some(7));
+        assert_eq!(
+            seat_map(layout, &passes),
+            "   0 -- --\n   1 -# #.\n   2 #. #-\n   3 -- --\n"
+        );
+    }
+}
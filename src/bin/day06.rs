@@ -1,14 +1,162 @@
-use std::{collections::HashSet, fs};
+use std::env;
+use std::fs;
+use std::str::FromStr;
 
-type Group = Vec<HashSet<char>>;
+const NUM_QUESTIONS: usize = 26;
 
-fn parse(input: &str) -> Vec<Group> {
+// set of answered questions 'a'..='z', question i is stored in bit i
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Answers(u32);
+
+impl Answers {
+    const ALL: Answers = Answers((1 << NUM_QUESTIONS) - 1);
+
+    fn len(self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    fn contains(self, question: usize) -> bool {
+        self.0 & (1 << question) != 0
+    }
+
+    fn union(self, other: Answers) -> Answers {
+        Answers(self.0 | other.0)
+    }
+
+    fn intersection(self, other: Answers) -> Answers {
+        Answers(self.0 & other.0)
+    }
+
+    fn questions(self) -> impl Iterator<Item = char> {
+        (0..NUM_QUESTIONS)
+            .filter(move |&q| self.contains(q))
+            .map(question_char)
+    }
+}
+
+impl FromStr for Answers {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.chars().try_fold(Answers::default(), |acc, c| match c {
+            'a'..='z' => Ok(Answers(acc.0 | 1 << (c as u32 - 'a' as u32))),
+            _ => Err(format!("invalid answer '{}'", c)),
+        })
+    }
+}
+
+fn question_char(question: usize) -> char {
+    (b'a' + question as u8) as char
+}
+
+type Group = Vec<Answers>;
+
+fn parse(input: &str) -> Result<Vec<Group>, String> {
     input
         .split("\n\n")
-        .map(|g| g.lines().map(|l| l.chars().collect()).collect())
+        .map(|g| g.lines().map(str::parse).collect())
         .collect()
 }
 
+// questions anyone in the group answered
+fn union(group: &[Answers]) -> Answers {
+    group
+        .iter()
+        .fold(Answers::default(), |acc, &a| acc.union(a))
+}
+
+// questions everyone in the group answered, nothing for an empty group
+fn intersection(group: &[Answers]) -> Answers {
+    if group.is_empty() {
+        return Answers::default();
+    }
+    group
+        .iter()
+        .fold(Answers::ALL, |acc, &a| acc.intersection(a))
+}
+
+// number of respondents per question
+fn histogram(group: &[Answers]) -> [usize; NUM_QUESTIONS] {
+    let mut hist = [0; NUM_QUESTIONS];
+    for answers in group {
+        for (q, count) in hist.iter_mut().enumerate() {
+            if answers.contains(q) {
+                *count += 1;
+            }
+        }
+    }
+    hist
+}
+
+// questions answered by at least k respondents of the group
+fn at_least(group: &[Answers], k: usize) -> Answers {
+    histogram(group)
+        .iter()
+        .enumerate()
+        .filter(|&(_, &count)| count >= k && count > 0)
+        .fold(Answers::default(), |acc, (q, _)| Answers(acc.0 | 1 << q))
+}
+
+// Returns the most and least common answers over all groups together with their
+// number of respondents. Questions nobody answered are not taken into account.
+fn most_and_least_common(groups: &[Group]) -> Option<((Answers, usize), (Answers, usize))> {
+    let mut total = [0; NUM_QUESTIONS];
+    for g in groups {
+        for (t, count) in total.iter_mut().zip(histogram(g).iter()) {
+            *t += count;
+        }
+    }
+
+    let answered = || total.iter().copied().filter(|&c| c > 0);
+    let max = answered().max()?;
+    let min = answered().min()?;
+    let with_count = |n: usize| {
+        total
+            .iter()
+            .enumerate()
+            .filter(|&(_, &c)| c == n)
+            .fold(Answers::default(), |acc, (q, _)| Answers(acc.0 | 1 << q))
+    };
+
+    Some(((with_count(max), max), (with_count(min), min)))
+}
+
+fn report(groups: &[Group]) -> String {
+    let mut out = String::new();
+
+    for (i, g) in groups.iter().enumerate() {
+        let hist = histogram(g);
+        let freq: Vec<String> = (0..NUM_QUESTIONS)
+            .filter(|&q| hist[q] > 0)
+            .map(|q| format!("{}:{}", question_char(q), hist[q]))
+            .collect();
+        let at_least_k: Vec<String> = (1..=g.len())
+            .map(|k| format!("{}/{}: {}", k, g.len(), at_least(g, k).len()))
+            .collect();
+
+        out += &format!(
+            "group {}: {} people, anyone {}, everyone {}\n  answers   {}\n  at least  {}\n",
+            i + 1,
+            g.len(),
+            union(g).len(),
+            intersection(g).len(),
+            freq.join(" "),
+            at_least_k.join(", ")
+        );
+    }
+
+    if let Some(((most, max), (least, min))) = most_and_least_common(groups) {
+        out += &format!(
+            "most common: {} ({} people)\nleast common: {} ({} people)\n",
+            most.questions().collect::<String>(),
+            max,
+            least.questions().collect::<String>(),
+            min
+        );
+    }
+    out
+}
+
 enum Part {
     Part1,
     Part2,
@@ -20,13 +168,9 @@ impl Solution {
     fn solve(groups: &[Group], part: Part) -> usize {
         groups
             .iter()
-            .map(|g| {
-                g.iter()
-                    .fold(g[0].clone(), |acc, s| match part {
-                        Part::Part1 => acc.union(s).cloned().collect(),
-                        Part::Part2 => acc.intersection(s).cloned().collect(),
-                    })
-                    .len()
+            .map(|g| match part {
+                Part::Part1 => union(g).len(),
+                Part::Part2 => intersection(g).len(),
             })
             .sum()
     }
@@ -34,8 +178,42 @@ impl Solution {
 
 fn main() {
     let input = fs::read_to_string("./input/day06.txt").expect("File not found!");
-    let groups = parse(&input);
+    let groups = parse(input.trim()).unwrap_or_else(|e| panic!("{}", e));
+
+    if env::args().any(|a| a == "--report") {
+        print!("{}", report(&groups));
+    }
 
     println!("p1: {}", Solution::solve(&groups, Part::Part1));
     println!("p2: {}", Solution::solve(&groups, Part::Part2));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = "abc\n\na\nb\nc\n\nab\nac\n\na\na\na\na\n\nb";
+
+    #[test]
+    fn test_day06() {
+        let groups = parse(INPUT).unwrap();
+        assert_eq!(Solution::solve(&groups, Part::Part1), 11);
+        assert_eq!(Solution::solve(&groups, Part::Part2), 6);
+        assert_eq!(intersection(&[]), Answers::default());
+        assert!(parse("ab\nA").is_err());
+    }
+
+    #[test]
+    fn test_day06_statistics() {
+        let groups = parse(INPUT).unwrap();
+
+        assert_eq!(histogram(&groups[2])[..3], [2, 1, 1]);
+        assert_eq!(at_least(&groups[2], 1), "abc".parse().unwrap());
+        assert_eq!(at_least(&groups[2], 2), "a".parse().unwrap());
+        assert_eq!(at_least(&groups[2], 3), Answers::default());
+        assert_eq!(
+            most_and_least_common(&groups),
+            Some((("a".parse().unwrap(), 8), ("c".parse().unwrap(), 3)))
+        );
+    }
+}
@@ -1,18 +1,22 @@
 use std::{
-    collections::{HashMap, HashSet},
-    fs,
+    cell::RefCell,
+    collections::{HashMap, HashSet, VecDeque},
+    env, fs, process,
+    rc::Rc,
 };
 
 type Bags = HashMap<String, HashSet<(u32, String)>>;
 
-fn parse(input: &str) -> Bags {
+fn parse(input: &str) -> Result<Bags, String> {
     let mut bags: Bags = HashMap::new();
 
-    for line in input.lines() {
+    for line in input.lines().filter(|l| !l.trim().is_empty()) {
         let mut bag_iter = line.split(" bags contain");
 
         let outer_bag = bag_iter.next().unwrap();
-        let inner_bag_list = bag_iter.next().unwrap();
+        let inner_bag_list = bag_iter
+            .next()
+            .ok_or_else(|| format!("invalid rule '{}'", line))?;
 
         let mut inner_bags: HashSet<(u32, String)> = HashSet::new();
 
@@ -27,51 +31,419 @@ fn parse(input: &str) -> Bags {
                 .replace(" bag", "");
 
             let bag: Vec<&str> = bag.splitn(2, ' ').collect();
-            let (n, color): (u32, String) = (bag[0].parse::<u32>().unwrap(), bag[1].to_owned());
+            let (n, color): (u32, String) = match (bag[0].parse::<u32>(), bag.get(1)) {
+                (Ok(n), Some(color)) => (n, (*color).to_owned()),
+                _ => {
+                    return Err(format!(
+                        "invalid bag '{}' in rule '{}'",
+                        bag.join(" "),
+                        line
+                    ))
+                }
+            };
 
             inner_bags.insert((n, color));
         }
         bags.insert(outer_bag.to_owned(), inner_bags);
     }
-    bags
+    Ok(bags)
 }
 
-const MY_BAG: &str = "shiny gold";
+type BagId = usize;
 
-struct Solution;
+// Containment graph over interned bag colors. Colors that only appear inside other
+// bags are leaves. Construction fails on cyclic rules, so every query terminates.
+struct BagGraph {
+    names: Vec<String>,
+    ids: HashMap<String, BagId>,
+    contents: Vec<Vec<(u64, BagId)>>,
+    containers: Vec<Vec<BagId>>,
+    // total number of bags inside each bag, None if it does not fit into u64
+    totals: Vec<Option<u64>>,
+    ancestors: RefCell<HashMap<BagId, Rc<HashSet<BagId>>>>,
+}
 
-impl Solution {
-    fn part1(bags: &Bags) -> u32 {
-        fn contains_shiny_gold(bag: &str, bags: &Bags) -> bool {
-            bags[bag]
+impl BagGraph {
+    fn new(bags: &Bags) -> Result<Self, String> {
+        let mut graph = BagGraph {
+            names: vec![],
+            ids: HashMap::new(),
+            contents: vec![],
+            containers: vec![],
+            totals: vec![],
+            ancestors: RefCell::new(HashMap::new()),
+        };
+
+        let mut outer_bags: Vec<&String> = bags.keys().collect();
+        outer_bags.sort();
+
+        for outer in outer_bags {
+            let outer_id = graph.intern(outer);
+            let mut inner_bags: Vec<&(u32, String)> = bags[outer].iter().collect();
+            inner_bags.sort();
+
+            for (n, inner) in inner_bags {
+                let inner_id = graph.intern(inner);
+                graph.contents[outer_id].push((*n as u64, inner_id));
+                graph.containers[inner_id].push(outer_id);
+            }
+        }
+
+        let order = graph.topological_order()?;
+        graph.totals = vec![Some(0); graph.names.len()];
+        for &id in order.iter().rev() {
+            graph.totals[id] = graph.contents[id]
                 .iter()
-                .any(|(_, b)| b == MY_BAG || contains_shiny_gold(b, bags))
+                .try_fold(0_u64, |acc, &(n, inner)| {
+                    let per_bag = graph.totals[inner]?.checked_add(1)?;
+                    acc.checked_add(n.checked_mul(per_bag)?)
+                });
+        }
+
+        Ok(graph)
+    }
+
+    fn intern(&mut self, color: &str) -> BagId {
+        if let Some(&id) = self.ids.get(color) {
+            return id;
         }
+        let id = self.names.len();
+        self.names.push(color.to_owned());
+        self.ids.insert(color.to_owned(), id);
+        self.contents.push(vec![]);
+        self.containers.push(vec![]);
+        id
+    }
 
-        bags.keys()
-            .filter(|bag| contains_shiny_gold(bag, bags))
-            .count() as u32
+    fn id(&self, color: &str) -> Result<BagId, String> {
+        self.ids
+            .get(color)
+            .copied()
+            .ok_or_else(|| format!("unknown bag color '{}'", color))
     }
 
-    fn part2(bags: &Bags) -> u32 {
-        fn sum_bags(bag: &str, bags: &Bags) -> u32 {
-            bags[bag]
+    // Kahn's algorithm, outer bags come before the bags they contain
+    fn topological_order(&self) -> Result<Vec<BagId>, String> {
+        let mut in_degree: Vec<usize> = self.containers.iter().map(Vec::len).collect();
+        let mut queue: Vec<BagId> = (0..self.names.len())
+            .filter(|&id| in_degree[id] == 0)
+            .collect();
+        let mut order = vec![];
+
+        while let Some(id) = queue.pop() {
+            order.push(id);
+            for &(_, inner) in self.contents[id].iter() {
+                in_degree[inner] -= 1;
+                if in_degree[inner] == 0 {
+                    queue.push(inner);
+                }
+            }
+        }
+
+        if order.len() == self.names.len() {
+            return Ok(order);
+        }
+
+        // Every bag left over lies on or below a cycle. Those below a cycle may be
+        // leaves, but each left over bag has a left over container, so following the
+        // containers upwards has to revisit a bag eventually.
+        let mut path = vec![];
+        let mut visited = HashMap::new();
+        let mut id = (0..self.names.len()).find(|&id| in_degree[id] > 0).unwrap();
+        while !visited.contains_key(&id) {
+            visited.insert(id, path.len());
+            path.push(id);
+            id = self.containers[id]
                 .iter()
-                .map(|(n, b)| n * sum_bags(b, bags))
-                .sum::<u32>()
-                + 1
+                .copied()
+                .find(|&outer| in_degree[outer] > 0)
+                .unwrap();
+        }
+        // the path runs from inner to outer bags, list the cycle the other way round
+        let cycle: Vec<&str> = path[visited[&id]..]
+            .iter()
+            .chain(std::iter::once(&id))
+            .rev()
+            .map(|&id| self.names[id].as_str())
+            .collect();
+
+        Err(format!("cyclic rules: {}", cycle.join(" -> ")))
+    }
+
+    // all bags that eventually contain the given bag
+    fn ancestors(&self, id: BagId) -> Rc<HashSet<BagId>> {
+        if let Some(known) = self.ancestors.borrow().get(&id) {
+            return Rc::clone(known);
+        }
+
+        let mut found = HashSet::new();
+        let mut stack = vec![id];
+        while let Some(bag) = stack.pop() {
+            for &outer in self.containers[bag].iter() {
+                if found.insert(outer) {
+                    stack.push(outer);
+                }
+            }
+        }
+
+        let found = Rc::new(found);
+        self.ancestors.borrow_mut().insert(id, Rc::clone(&found));
+        found
+    }
+
+    // number of bags inside the given bag
+    fn total(&self, id: BagId) -> Result<u64, String> {
+        self.totals[id]
+            .ok_or_else(|| format!("'{}' holds more than {} bags", self.names[id], u64::MAX))
+    }
+}
+
+impl BagGraph {
+    fn names(&self, ids: impl IntoIterator<Item = BagId>) -> Vec<&str> {
+        let mut names: Vec<&str> = ids.into_iter().map(|id| self.names[id].as_str()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    // bags that can eventually contain the given color
+    fn containers_of(&self, color: &str) -> Result<Vec<&str>, String> {
+        Ok(self.names(self.ancestors(self.id(color)?).iter().copied()))
+    }
+
+    // number of bags the given color holds
+    fn count_inside(&self, color: &str) -> Result<u64, String> {
+        self.total(self.id(color)?)
+    }
+
+    // Shortest chain of rules from `outer` down to `inner`, every step gives the
+    // number of bags of that color held by the previous one. None if `outer` can't
+    // contain `inner`.
+    fn nesting_path(&self, outer: &str, inner: &str) -> Result<Option<Vec<(u64, &str)>>, String> {
+        let (from, to) = (self.id(outer)?, self.id(inner)?);
+
+        let mut parent: HashMap<BagId, (u64, BagId)> = HashMap::new();
+        let mut queue = VecDeque::from(vec![from]);
+        while let Some(bag) = queue.pop_front() {
+            if bag == to && bag != from {
+                break;
+            }
+            for &(n, next) in self.contents[bag].iter() {
+                if next != from && !parent.contains_key(&next) {
+                    parent.insert(next, (n, bag));
+                    queue.push_back(next);
+                }
+            }
         }
 
-        sum_bags(MY_BAG, bags) - 1_u32
+        if !parent.contains_key(&to) {
+            return Ok(None);
+        }
+        let mut path = vec![];
+        let mut bag = to;
+        while let Some(&(n, prev)) = parent.get(&bag) {
+            path.push((n, self.names[bag].as_str()));
+            if prev == from {
+                break;
+            }
+            bag = prev;
+        }
+        path.push((1, self.names[from].as_str()));
+        path.reverse();
+        Ok(Some(path))
+    }
+
+    // bags that don't contain any other bags
+    fn leaves(&self) -> Vec<&str> {
+        self.names((0..self.names.len()).filter(|&id| self.contents[id].is_empty()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Direction {
+    // bags inside the focused bag
+    Down,
+    // bags containing the focused bag
+    Up,
+}
+
+#[derive(Debug, Default)]
+struct DotOptions {
+    focus: Option<(String, Direction)>,
+    max_depth: Option<usize>,
+    // leave out everything outside the focused subgraph
+    focus_only: bool,
+}
+
+impl BagGraph {
+    // bags reachable from `id` in the given direction within `max_depth` rules
+    fn reachable(
+        &self,
+        id: BagId,
+        direction: Direction,
+        max_depth: Option<usize>,
+    ) -> HashSet<BagId> {
+        let mut found: HashSet<BagId> = [id].iter().copied().collect();
+        let mut frontier = vec![id];
+        let mut depth = 0;
+
+        while !frontier.is_empty() && max_depth.is_none_or(|max| depth < max) {
+            let mut next = vec![];
+            for bag in frontier {
+                let neighbours: Vec<BagId> = match direction {
+                    Direction::Down => self.contents[bag].iter().map(|&(_, b)| b).collect(),
+                    Direction::Up => self.containers[bag].clone(),
+                };
+                next.extend(neighbours.into_iter().filter(|&b| found.insert(b)));
+            }
+            frontier = next;
+            depth += 1;
+        }
+        found
+    }
+
+    fn to_dot(&self, options: &DotOptions) -> Result<String, String> {
+        let (focus, highlighted) = match &options.focus {
+            Some((color, direction)) => {
+                let id = self.id(color)?;
+                (Some(id), self.reachable(id, *direction, options.max_depth))
+            }
+            None => (None, HashSet::new()),
+        };
+        let shown = |id: BagId| !options.focus_only || highlighted.contains(&id);
+
+        let mut order: Vec<BagId> = (0..self.names.len()).collect();
+        order.sort_by_key(|&id| &self.names[id]);
+
+        let mut dot = String::from("digraph bags {\n    rankdir=LR;\n    node [shape=box];\n");
+        for &id in order.iter() {
+            if !shown(id) {
+                continue;
+            }
+            let style = if Some(id) == focus {
+                " [style=filled, fillcolor=gold]"
+            } else if highlighted.contains(&id) {
+                " [style=filled, fillcolor=lightblue]"
+            } else {
+                ""
+            };
+            dot += &format!("    \"{}\"{};\n", self.names[id], style);
+        }
+        for &outer in order.iter() {
+            for &(n, inner) in self.contents[outer].iter() {
+                if !shown(outer) || !shown(inner) {
+                    continue;
+                }
+                let style = if highlighted.contains(&outer) && highlighted.contains(&inner) {
+                    ", color=blue, penwidth=2"
+                } else {
+                    ""
+                };
+                dot += &format!(
+                    "    \"{}\" -> \"{}\" [label=\"{}\"{}];\n",
+                    self.names[outer], self.names[inner], n, style
+                );
+            }
+        }
+        dot += "}\n";
+        Ok(dot)
+    }
+}
+
+fn parse_dot_options(args: &[&str]) -> Result<DotOptions, String> {
+    let mut options = DotOptions::default();
+    let mut args = args.iter();
+
+    while let Some(&arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .copied()
+                .ok_or(format!("missing value for {}", arg))
+        };
+        match arg {
+            "--from" => options.focus = Some((value()?.to_owned(), Direction::Down)),
+            "--to" => options.focus = Some((value()?.to_owned(), Direction::Up)),
+            "--depth" => {
+                let depth = value()?;
+                options.max_depth = Some(
+                    depth
+                        .parse()
+                        .map_err(|_| format!("invalid depth '{}'", depth))?,
+                );
+            }
+            "--only" => options.focus_only = true,
+            _ => return Err(format!("unknown dot option '{}'", arg)),
+        }
+    }
+
+    // depth and subgraph are measured from the focused bag
+    if options.focus.is_none() && (options.max_depth.is_some() || options.focus_only) {
+        return Err("--depth and --only need --from or --to".to_string());
+    }
+    Ok(options)
+}
+
+const MY_BAG: &str = "shiny gold";
+
+struct Solution;
+
+impl Solution {
+    fn part1(graph: &BagGraph, color: &str) -> Result<usize, String> {
+        Ok(graph.containers_of(color)?.len())
+    }
+
+    fn part2(graph: &BagGraph, color: &str) -> Result<u64, String> {
+        graph.count_inside(color)
+    }
+}
+
+fn run_query(graph: &BagGraph, query: &[String]) -> Result<String, String> {
+    let query: Vec<&str> = query.iter().map(String::as_str).collect();
+
+    match query.as_slice() {
+        ["containers", color] => Ok(graph.containers_of(color)?.join("\n")),
+        ["count", color] => Ok(graph.count_inside(color)?.to_string()),
+        ["path", outer, inner] => match graph.nesting_path(outer, inner)? {
+            Some(path) => Ok(path
+                .iter()
+                .map(|(n, color)| format!("{} {}", n, color))
+                .collect::<Vec<_>>()
+                .join(" -> ")),
+            None => Err(format!("'{}' can't contain '{}'", outer, inner)),
+        },
+        ["leaves"] => Ok(graph.leaves().join("\n")),
+        ["dot", options @ ..] => graph.to_dot(&parse_dot_options(options)?),
+        _ => Err(String::from(
+            "usage: day07 [--rules <file>] [containers <color> | count <color> | path <outer> <inner> | leaves | \
+             dot [--from <color> | --to <color>] [--depth <n>] [--only]]",
+        )),
     }
 }
 
 fn main() {
-    let input = fs::read_to_string("./input/day07.txt").expect("File not found!");
-    let bags = parse(&input);
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let mut rules_file = String::from("./input/day07.txt");
+    if let Some(i) = args.iter().position(|a| a == "--rules") {
+        rules_file = args.get(i + 1).expect("missing rules file").clone();
+        args.drain(i..i + 2);
+    }
+
+    let input = fs::read_to_string(&rules_file).expect("File not found!");
+    let graph = parse(&input)
+        .and_then(|bags| BagGraph::new(&bags))
+        .unwrap_or_else(|e| panic!("{}", e));
 
-    println!("p1: {}", Solution::part1(&bags));
-    println!("p2: {}", Solution::part2(&bags));
+    if args.is_empty() {
+        println!("p1: {}", Solution::part1(&graph, MY_BAG).unwrap());
+        println!("p2: {}", Solution::part2(&graph, MY_BAG).unwrap());
+    } else {
+        match run_query(&graph, &args) {
+            Ok(answer) => println!("{}", answer),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -91,8 +463,12 @@ dark olive bags contain 3 faded blue bags, 4 dotted black bags.
 vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.
 faded blue bags contain no other bags.
 dotted black bags contain no other bags.",
+        )
+        .unwrap();
+        assert_eq!(
+            Solution::part1(&BagGraph::new(&bags).unwrap(), MY_BAG),
+            Ok(4)
         );
-        assert_eq!(Solution::part1(&bags), 4);
     }
 
     #[test]
@@ -106,7 +482,135 @@ dark yellow bags contain 2 dark green bags.
 dark green bags contain 2 dark blue bags.
 dark blue bags contain 2 dark violet bags.
 dark violet bags contain no other bags.",
+        )
+        .unwrap();
+        assert_eq!(
+            Solution::part2(&BagGraph::new(&bags).unwrap(), MY_BAG),
+            Ok(126)
+        );
+    }
+
+    #[test]
+    fn test_day07_queries() {
+        let bags = parse(
+            "\
+light red bags contain 1 bright white bag, 2 muted yellow bags.
+bright white bags contain 1 shiny gold bag.
+muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.
+shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags.
+dark olive bags contain 3 faded blue bags, 4 dotted black bags.
+vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.",
+        )
+        .unwrap();
+        let graph = BagGraph::new(&bags).unwrap();
+
+        assert_eq!(
+            graph.containers_of("dark olive"),
+            Ok(vec![
+                "bright white",
+                "light red",
+                "muted yellow",
+                "shiny gold"
+            ])
+        );
+        assert_eq!(graph.count_inside("dark olive"), Ok(7));
+        assert_eq!(
+            graph.nesting_path("light red", "dotted black"),
+            Ok(Some(vec![
+                (1, "light red"),
+                (1, "bright white"),
+                (1, "shiny gold"),
+                (1, "dark olive"),
+                (4, "dotted black")
+            ]))
+        );
+        assert_eq!(graph.nesting_path("dark olive", "light red"), Ok(None));
+        assert_eq!(graph.leaves(), vec!["dotted black", "faded blue"]);
+        assert!(parse("shiny gold bags contain two red bags.").is_err());
+    }
+
+    #[test]
+    fn test_day07_dot() {
+        let bags = parse(
+            "\
+bright white bags contain 1 shiny gold bag.
+shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags.
+dark olive bags contain 4 dotted black bags.",
+        )
+        .unwrap();
+        let graph = BagGraph::new(&bags).unwrap();
+
+        let options =
+            parse_dot_options(&["--from", "shiny gold", "--depth", "1", "--only"]).unwrap();
+        assert_eq!(
+            graph.to_dot(&options),
+            Ok(String::from(
+                "\
+digraph bags {
+    rankdir=LR;
+    node [shape=box];
+    \"dark olive\" [style=filled, fillcolor=lightblue];
+    \"shiny gold\" [style=filled, fillcolor=gold];
+    \"vibrant plum\" [style=filled, fillcolor=lightblue];
+    \"shiny gold\" -> \"dark olive\" [label=\"1\", color=blue, penwidth=2];
+    \"shiny gold\" -> \"vibrant plum\" [label=\"2\", color=blue, penwidth=2];
+}
+"
+            ))
+        );
+
+        let dot = graph
+            .to_dot(&parse_dot_options(&["--to", "dark olive"]).unwrap())
+            .unwrap();
+        assert!(dot.contains("\"bright white\" [style=filled, fillcolor=lightblue];"));
+        assert!(dot.contains("\"dark olive\" -> \"dotted black\" [label=\"4\"];"));
+        assert!(parse_dot_options(&["--depth"]).is_err());
+        assert!(parse_dot_options(&["--depth", "2"]).is_err());
+        assert!(parse_dot_options(&["--only"]).is_err());
+    }
+
+    #[test]
+    fn test_day07_cycle_and_overflow() {
+        let bags = parse(
+            "\
+shiny gold bags contain 2 dark red bags.
+dark red bags contain 1 dark blue bag, 3 faded blue bags.
+dark blue bags contain 1 shiny gold bag.",
+        )
+        .unwrap();
+        assert_eq!(
+            BagGraph::new(&bags).err(),
+            Some(String::from(
+                "cyclic rules: dark blue -> shiny gold -> dark red -> dark blue"
+            ))
+        );
+
+        // a leaf below the cycle is left over as well
+        let bags = parse(
+            "\
+a bags contain no other bags.
+b bags contain 1 a bag, 1 c bag.
+c bags contain 1 b bag.",
+        )
+        .unwrap();
+        assert_eq!(
+            BagGraph::new(&bags).err(),
+            Some(String::from("cyclic rules: b -> c -> b"))
+        );
+
+        let bags = parse(
+            "\
+shiny gold bags contain 4294967295 dark red bags.
+dark red bags contain 4294967295 dark blue bags.
+dark blue bags contain 4294967295 faded blue bags.",
+        )
+        .unwrap();
+        let graph = BagGraph::new(&bags).unwrap();
+        assert_eq!(
+            graph.total(graph.id("dark red").unwrap()),
+            Ok(18446744069414584320)
         );
-        assert_eq!(Solution::part2(&bags), 126);
+        assert!(Solution::part2(&graph, MY_BAG).is_err());
+        assert!(graph.id("dotted black").is_err());
     }
 }
@@ -1,64 +1,191 @@
-use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::env;
 use std::fs;
+use std::io::{self, BufRead};
 
 fn parse(input: &str) -> Vec<usize> {
     input.lines().flat_map(str::parse).collect()
 }
 
-fn find_invalid_number(data: &[usize], wlen: usize) -> Option<usize> {
-    'outer: for w in data.windows(wlen + 1) {
-        let target = w[wlen];
+// The last `len` numbers of the stream together with a multiset of their values, so
+// that checking for a pair summing up to a target takes O(len) and sliding the
+// window takes O(1).
+struct SumWindow {
+    len: usize,
+    values: VecDeque<usize>,
+    counts: HashMap<usize, usize>,
+}
 
-        for i in 0..wlen {
-            for j in 0..wlen {
-                if w[i] + w[j] == target && i != j {
-                    continue 'outer;
-                }
+impl SumWindow {
+    fn new(len: usize) -> Self {
+        Self {
+            len,
+            values: VecDeque::with_capacity(len + 1),
+            counts: HashMap::with_capacity(len + 1),
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.values.len() == self.len
+    }
+
+    fn push(&mut self, value: usize) {
+        self.values.push_back(value);
+        *self.counts.entry(value).or_insert(0) += 1;
+
+        if self.values.len() > self.len {
+            let oldest = self.values.pop_front().unwrap();
+            let count = self.counts.get_mut(&oldest).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                self.counts.remove(&oldest);
             }
         }
-        return Some(target);
     }
-    None
+
+    // true if two numbers at different positions of the window sum up to `target`
+    fn has_pair_sum(&self, target: usize) -> bool {
+        self.counts.iter().any(|(&v, &count)| {
+            v <= target
+                && match self.counts.get(&(target - v)) {
+                    Some(_) if target - v != v => true,
+                    Some(_) => count >= 2,
+                    None => false,
+                }
+        })
+    }
 }
 
-fn find_enc_weakness(data: &[usize], target: usize) -> usize {
-    let mut lower = 0;
-    let mut upper = 1;
+#[derive(Debug, PartialEq)]
+struct InvalidNumber {
+    position: usize,
+    value: usize,
+}
+
+// Validates a possibly unbounded stream of numbers, yielding every number that isn't
+// the sum of two of the `preamble` numbers before it as soon as it is read.
+struct Validator<I> {
+    numbers: I,
+    window: SumWindow,
+    position: usize,
+}
 
-    loop {
-        match data[lower..upper].iter().sum::<usize>().cmp(&target) {
-            Ordering::Less => upper += 1,
-            Ordering::Equal => break,
-            Ordering::Greater => lower += 1,
+impl<I: Iterator<Item = usize>> Validator<I> {
+    fn new(numbers: I, preamble: usize) -> Self {
+        Self {
+            numbers,
+            window: SumWindow::new(preamble),
+            position: 0,
         }
     }
+}
+
+impl<I: Iterator<Item = usize>> Iterator for Validator<I> {
+    type Item = InvalidNumber;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for value in self.numbers.by_ref() {
+            let position = self.position;
+            let valid = !self.window.is_full() || self.window.has_pair_sum(value);
+
+            self.window.push(value);
+            self.position += 1;
+
+            if !valid {
+                return Some(InvalidNumber { position, value });
+            }
+        }
+        None
+    }
+}
+
+// Reads one number per line, stops at the first I/O error
+fn read_numbers<R: BufRead>(reader: R) -> impl Iterator<Item = usize> {
+    reader
+        .lines()
+        .map_while(Result::ok)
+        .flat_map(|line| line.trim().parse())
+}
 
-    let (min, max) = data[lower..upper]
-        .iter()
-        .fold((usize::MAX, 0), |(min, max), &v| (v.min(min), v.max(max)));
+fn find_invalid_numbers(data: &[usize], preamble: usize) -> Vec<InvalidNumber> {
+    Validator::new(data.iter().copied(), preamble).collect()
+}
 
-    min + max
+fn find_invalid_number(data: &[usize], preamble: usize) -> Option<usize> {
+    Validator::new(data.iter().copied(), preamble)
+        .next()
+        .map(|invalid| invalid.value)
+}
+
+// Sum of the smallest and largest number of a contiguous range of at least two
+// numbers summing up to `target`, None if there is no such range
+fn find_enc_weakness(data: &[usize], target: usize) -> Option<usize> {
+    let mut lower = 0;
+    let mut sum = 0;
+
+    for upper in 0..data.len() {
+        sum += data[upper];
+        while sum > target && lower <= upper {
+            sum -= data[lower];
+            lower += 1;
+        }
+
+        if sum == target && upper > lower {
+            let (min, max) = data[lower..=upper]
+                .iter()
+                .fold((usize::MAX, 0), |(min, max), &v| (v.min(min), v.max(max)));
+            return Some(min + max);
+        }
+    }
+    None
 }
 
 struct Solution;
 
 impl Solution {
-    fn part1(data: &[usize]) -> usize {
-        find_invalid_number(data, 25).unwrap()
+    fn part1(data: &[usize], preamble: usize) -> Option<usize> {
+        find_invalid_number(data, preamble)
     }
 
-    fn part2(data: &[usize], target: usize) -> usize {
+    fn part2(data: &[usize], target: usize) -> Option<usize> {
         find_enc_weakness(data, target)
     }
 }
 
 fn main() {
+    // usage: day09 [--preamble <n>] [--all | --stream]
+    let args: Vec<String> = env::args().skip(1).collect();
+    let preamble = match args.iter().position(|a| a == "--preamble") {
+        Some(i) => args
+            .get(i + 1)
+            .and_then(|n| n.parse().ok())
+            .expect("invalid preamble length"),
+        None => 25,
+    };
+
+    if args.iter().any(|a| a == "--stream") {
+        let stdin = io::stdin();
+        for invalid in Validator::new(read_numbers(stdin.lock()), preamble) {
+            println!("invalid number at {}: {}", invalid.position, invalid.value);
+        }
+        return;
+    }
+
     let input = fs::read_to_string("./input/day09.txt").expect("File not found!");
     let data = parse(&input);
 
-    let invalid_number = Solution::part1(&data);
+    if args.iter().any(|a| a == "--all") {
+        for invalid in find_invalid_numbers(&data, preamble) {
+            println!("invalid number at {}: {}", invalid.position, invalid.value);
+        }
+    }
+
+    let invalid_number = Solution::part1(&data, preamble).expect("No invalid number found!");
     println!("p1: {}", invalid_number);
-    println!("p2: {}", Solution::part2(&data, invalid_number));
+    println!(
+        "p2: {}",
+        Solution::part2(&data, invalid_number).expect("No encryption weakness found!")
+    );
 }
 
 #[cfg(test)]
@@ -90,6 +217,55 @@ mod tests {
 576";
         let data = parse(input);
         assert_eq!(find_invalid_number(&data, 5), Some(127));
-        assert_eq!(find_enc_weakness(&data, 127), 62);
+        assert_eq!(find_enc_weakness(&data, 127), Some(62));
+        assert_eq!(
+            find_invalid_numbers(&data, 5),
+            vec![InvalidNumber {
+                position: 14,
+                value: 127
+            }]
+        );
+    }
+
+    #[test]
+    fn test_day09_stream() {
+        let input = "1\n2\n3\n10\n13\n4\n";
+        let mut events = Validator::new(read_numbers(input.as_bytes()), 2);
+        assert_eq!(
+            events.next(),
+            Some(InvalidNumber {
+                position: 3,
+                value: 10
+            })
+        );
+        assert_eq!(
+            events.next(),
+            Some(InvalidNumber {
+                position: 5,
+                value: 4
+            })
+        );
+        assert_eq!(events.next(), None);
+
+        // only as much of an unbounded stream is consumed as needed
+        assert_eq!(
+            Validator::new(1.., 3).next(),
+            Some(InvalidNumber {
+                position: 5,
+                value: 6
+            })
+        );
+    }
+
+    #[test]
+    fn test_day09_edge_cases() {
+        // equal values at different positions form a valid pair
+        assert_eq!(find_invalid_number(&[1, 2, 2, 4], 3), None);
+        assert_eq!(find_invalid_number(&[1, 2, 3, 6], 3), Some(6));
+
+        // a single number is no range, and there might be no range at all
+        assert_eq!(find_enc_weakness(&[5, 1, 9], 9), None);
+        assert_eq!(find_enc_weakness(&[5, 1, 9, 3], 12), Some(12));
+        assert_eq!(find_enc_weakness(&[], 3), None);
     }
 }
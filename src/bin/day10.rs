@@ -1,70 +1,278 @@
+use std::collections::BTreeMap;
+use std::env;
 use std::fs;
 
 fn parse(input: &str) -> Vec<usize> {
     input.lines().flat_map(str::parse).collect()
 }
 
-fn build_chain(adapters: &mut Vec<usize>) {
+fn build_chain(adapters: &mut Vec<usize>, max_step: usize) {
     // add charging outlet's effective rating
     adapters.push(0);
 
     adapters.sort_unstable();
 
     // add device's built-in adapter
-    adapters.push(adapters[adapters.len() - 1] + 3);
+    adapters.push(adapters[adapters.len() - 1] + max_step);
 }
 
-fn get_diffs(adapters: &[usize]) -> Vec<usize> {
-    adapters.windows(2).map(|pair| pair[1] - pair[0]).collect()
+// number of adapter pairs per joltage difference along the chain
+fn diff_distribution(adapters: &[usize]) -> BTreeMap<usize, usize> {
+    let mut dist = BTreeMap::new();
+    for pair in adapters.windows(2) {
+        *dist.entry(pair[1] - pair[0]).or_insert(0) += 1;
+    }
+    dist
+}
+
+// Number of ways to get from the first to the last adapter of the sorted chain when
+// each adapter accepts inputs up to `max_step` jolts lower. None if the count
+// doesn't fit into a u128.
+fn count_arrangements(adapters: &[usize], max_step: usize) -> Option<u128> {
+    let mut ways: Vec<u128> = vec![0; adapters.len()];
+    ways[0] = 1;
+
+    for i in 1..adapters.len() {
+        let mut sum: u128 = 0;
+        for j in (0..i).rev() {
+            if adapters[i] - adapters[j] > max_step {
+                break;
+            }
+            sum = sum.checked_add(ways[j])?;
+        }
+        ways[i] = sum;
+    }
+    ways.last().copied()
+}
+
+// every adapter can take its input from the one before it
+fn is_connected(adapters: &[usize], max_step: usize) -> bool {
+    adapters
+        .windows(2)
+        .all(|pair| pair[1] - pair[0] <= max_step)
+}
+
+// Lazily enumerates all valid arrangements of a sorted chain, in lexicographic order
+// of the adapter positions used. As long as the chain is connected, any partial
+// arrangement can be completed by taking every following adapter.
+struct Arrangements<'a> {
+    adapters: &'a [usize],
+    max_step: usize,
+    path: Vec<usize>,
+}
+
+impl<'a> Arrangements<'a> {
+    fn new(adapters: &'a [usize], max_step: usize) -> Self {
+        Self {
+            adapters,
+            max_step,
+            path: vec![],
+        }
+    }
+
+    fn reachable(&self, from: usize, to: usize) -> bool {
+        to < self.adapters.len() && self.adapters[to] - self.adapters[from] <= self.max_step
+    }
+
+    fn complete_path(&mut self) {
+        let last = self.adapters.len() - 1;
+        while self.path[self.path.len() - 1] != last {
+            self.path.push(self.path[self.path.len() - 1] + 1);
+        }
+    }
+}
+
+impl Iterator for Arrangements<'_> {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.path.is_empty() {
+            if self.adapters.is_empty() || !is_connected(self.adapters, self.max_step) {
+                return None;
+            }
+            self.path.push(0);
+        } else {
+            // advance the deepest adapter that may be swapped for a later one
+            let k = (1..self.path.len() - 1)
+                .rev()
+                .find(|&k| self.reachable(self.path[k - 1], self.path[k] + 1))?;
+            self.path[k] += 1;
+            self.path.truncate(k + 1);
+        }
+        self.complete_path();
+
+        Some(self.path.iter().map(|&i| self.adapters[i]).collect())
+    }
+}
+
+// Number of ways from each adapter of the sorted chain to the last one, None if the
+// counts don't fit into a u128.
+fn ways_to_end(adapters: &[usize], max_step: usize) -> Option<Vec<u128>> {
+    let mut ways: Vec<u128> = vec![0; adapters.len()];
+    *ways.last_mut()? = 1;
+
+    for i in (0..adapters.len() - 1).rev() {
+        let mut sum: u128 = 0;
+        for j in i + 1..adapters.len() {
+            if adapters[j] - adapters[i] > max_step {
+                break;
+            }
+            sum = sum.checked_add(ways[j])?;
+        }
+        ways[i] = sum;
+    }
+    Some(ways)
+}
+
+// SplitMix64, enough to draw reproducible samples without pulling in a crate
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // uniformly distributed in 0..bound, bound must not be 0
+    fn below(&mut self, bound: u128) -> u128 {
+        if bound == 1 {
+            return 0;
+        }
+        let mask = u128::MAX >> (bound - 1).leading_zeros();
+        loop {
+            let r = ((self.next_u64() as u128) << 64 | self.next_u64() as u128) & mask;
+            if r < bound {
+                return r;
+            }
+        }
+    }
 }
 
-fn get_diff_one_sequences(adapters: &[usize]) -> Vec<Vec<usize>> {
-    get_diffs(adapters)
-        .split(|&val| val == 3)
-        .map(|e| e.to_vec())
-        .collect()
+// Picks one of all valid arrangements with equal probability
+fn sample_arrangement(
+    adapters: &[usize],
+    max_step: usize,
+    seed: u64,
+) -> Result<Vec<usize>, String> {
+    if adapters.is_empty() {
+        return Err("no adapters to sample from".to_string());
+    }
+    if !is_connected(adapters, max_step) {
+        return Err("chain is not connected".to_string());
+    }
+    let ways = ways_to_end(adapters, max_step)
+        .ok_or_else(|| format!("too many arrangements to sample (more than {})", u128::MAX))?;
+
+    let mut rng = Rng(seed);
+    let mut i = 0;
+    let mut chain = vec![adapters[0]];
+    while i < adapters.len() - 1 {
+        // the next adapter is chosen by the share of arrangements continuing with it
+        let mut r = rng.below(ways[i]);
+        let mut j = i + 1;
+        while r >= ways[j] {
+            r -= ways[j];
+            j += 1;
+        }
+        i = j;
+        chain.push(adapters[i]);
+    }
+    Ok(chain)
+}
+
+fn shortest_arrangement(adapters: &[usize], max_step: usize) -> Option<Vec<usize>> {
+    if adapters.is_empty() || !is_connected(adapters, max_step) {
+        return None;
+    }
+
+    // always jumping as far as possible gives the fewest adapters
+    let mut i = 0;
+    let mut chain = vec![adapters[0]];
+    while i < adapters.len() - 1 {
+        i = (i + 1..adapters.len())
+            .take_while(|&j| adapters[j] - adapters[i] <= max_step)
+            .last()
+            .unwrap();
+        chain.push(adapters[i]);
+    }
+    Some(chain)
+}
+
+fn longest_arrangement(adapters: &[usize], max_step: usize) -> Option<Vec<usize>> {
+    Arrangements::new(adapters, max_step).next()
 }
 
 struct Solution;
 
 impl Solution {
     fn part1(adapters: &[usize]) -> usize {
-        let (ones, threes) =
-            adapters
-                .windows(2)
-                .fold((0, 0), |(ones, threes), pair| match pair[1] - pair[0] {
-                    1 => (ones + 1, threes),
-                    3 => (ones, threes + 1),
-                    _ => (ones, threes),
-                });
-        ones * threes
-    }
-
-    fn part2(adapters: &[usize]) -> usize {
-        // number of possible combinations/paths per diff-one-sequence's len
-        // 0 -> 1
-        // 1 -> 1
-        // 2 -> 2
-        // 3 -> 4
-        // 4 -> 7
-        // ...
-        let num_combinations = [1, 1, 2, 4, 7];
-        let one_sequences = get_diff_one_sequences(adapters);
-
-        one_sequences
-            .iter()
-            .map(|seq| num_combinations[seq.len()])
-            .product()
+        let dist = diff_distribution(adapters);
+        dist.get(&1).unwrap_or(&0) * dist.get(&3).unwrap_or(&0)
+    }
+
+    fn part2(adapters: &[usize]) -> u128 {
+        count_arrangements(adapters, 3).expect("Too many arrangements!")
     }
 }
 
 fn main() {
     let input = fs::read_to_string("./input/day10.txt").expect("File not found!");
     let mut adapters = parse(&input);
-    build_chain(&mut adapters);
 
-    println!("p1: {}", Solution::part1(&adapters));
-    println!("p2: {}", Solution::part2(&adapters));
+    // usage: day10 [--max-step <n>] [--list <n>] [--sample <seed>] [--extremes]
+    let args: Vec<String> = env::args().skip(1).collect();
+    let option = |name: &str| -> Option<u64> {
+        let i = args.iter().position(|a| a == name)?;
+        Some(
+            args.get(i + 1)
+                .and_then(|n| n.parse().ok())
+                .unwrap_or_else(|| panic!("invalid value for {}", name)),
+        )
+    };
+    let format_chain = |chain: &[usize]| {
+        let chain: Vec<String> = chain.iter().map(usize::to_string).collect();
+        format!("{} adapters: {}", chain.len(), chain.join(" "))
+    };
+
+    let max_step = option("--max-step").map_or(3, |n| n as usize);
+    build_chain(&mut adapters, max_step);
+
+    if let Some(n) = option("--list") {
+        for chain in Arrangements::new(&adapters, max_step).take(n as usize) {
+            println!("{}", format_chain(&chain));
+        }
+    }
+    if let Some(seed) = option("--sample") {
+        match sample_arrangement(&adapters, max_step, seed) {
+            Ok(chain) => println!("sample: {}", format_chain(&chain)),
+            Err(e) => println!("sample: {}", e),
+        }
+    }
+    if args.iter().any(|a| a == "--extremes") {
+        if let (Some(shortest), Some(longest)) = (
+            shortest_arrangement(&adapters, max_step),
+            longest_arrangement(&adapters, max_step),
+        ) {
+            println!("shortest: {}", format_chain(&shortest));
+            println!("longest: {}", format_chain(&longest));
+        }
+    }
+
+    if option("--max-step").is_some() {
+        for (diff, count) in diff_distribution(&adapters) {
+            println!("{} jolt differences: {}", diff, count);
+        }
+        match count_arrangements(&adapters, max_step) {
+            Some(n) => println!("arrangements: {}", n),
+            None => println!("arrangements: more than {}", u128::MAX),
+        }
+    } else {
+        println!("p1: {}", Solution::part1(&adapters));
+        println!("p2: {}", Solution::part2(&adapters));
+    }
 }
 
 #[cfg(test)]
@@ -86,7 +294,7 @@ mod tests {
 12
 4";
         let mut adapters = parse(input1);
-        build_chain(&mut adapters);
+        build_chain(&mut adapters, 3);
 
         assert_eq!(Solution::part1(&adapters), 35);
         assert_eq!(Solution::part2(&adapters), 8);
@@ -125,9 +333,68 @@ mod tests {
 3";
 
         adapters = parse(input2);
-        build_chain(&mut adapters);
+        build_chain(&mut adapters, 3);
 
         assert_eq!(Solution::part1(&adapters), 220);
         assert_eq!(Solution::part2(&adapters), 19208);
     }
+
+    #[test]
+    fn test_day10_general_steps() {
+        // a run of six diff-1 adapters
+        let adapters = [0, 1, 2, 3, 4, 5, 6, 9];
+        assert_eq!(count_arrangements(&adapters, 3), Some(24));
+
+        // diff-2 gaps and other maximum steps
+        let adapters = [0, 2, 4, 5, 7];
+        assert_eq!(count_arrangements(&adapters, 3), Some(3));
+        assert_eq!(count_arrangements(&adapters, 2), Some(1));
+        assert_eq!(count_arrangements(&adapters, 1), Some(0));
+        assert_eq!(count_arrangements(&adapters, 7), Some(8));
+        assert_eq!(
+            diff_distribution(&adapters).into_iter().collect::<Vec<_>>(),
+            vec![(1, 1), (2, 3)]
+        );
+
+        let adapters: Vec<usize> = (0..300).collect();
+        assert_eq!(count_arrangements(&adapters, 2).map(|_| ()), None);
+    }
+
+    #[test]
+    fn test_day10_arrangements() {
+        let adapters = [0, 1, 2, 3, 6];
+        let all: Vec<Vec<usize>> = Arrangements::new(&adapters, 3).collect();
+        assert_eq!(
+            all,
+            vec![
+                vec![0, 1, 2, 3, 6],
+                vec![0, 1, 3, 6],
+                vec![0, 2, 3, 6],
+                vec![0, 3, 6]
+            ]
+        );
+        assert_eq!(Arrangements::new(&[0, 4, 7], 3).next(), None);
+
+        assert_eq!(shortest_arrangement(&adapters, 3), Some(vec![0, 3, 6]));
+        assert_eq!(longest_arrangement(&adapters, 3), Some(vec![0, 1, 2, 3, 6]));
+
+        // the same seed gives the same sample, and every sample is a valid arrangement
+        let sample = sample_arrangement(&adapters, 3, 42).unwrap();
+        assert_eq!(sample_arrangement(&adapters, 3, 42), Ok(sample));
+        let mut seen = vec![0; all.len()];
+        for seed in 0..400 {
+            let sample = sample_arrangement(&adapters, 3, seed).unwrap();
+            seen[all.iter().position(|a| *a == sample).unwrap()] += 1;
+        }
+        assert!(seen.iter().all(|&n| n > 50));
+
+        assert_eq!(
+            sample_arrangement(&[0, 4, 7], 3, 0),
+            Err("chain is not connected".to_string())
+        );
+        let adapters: Vec<usize> = (0..300).collect();
+        assert!(sample_arrangement(&adapters, 2, 0)
+            .unwrap_err()
+            .starts_with("too many arrangements"));
+    }
 }
@@ -1,102 +1,412 @@
+use std::env;
+use std::fmt;
 use std::fs;
+use std::io;
+use std::mem;
+use std::path::Path;
+use std::time::Instant;
 
-type Grid = Vec<Vec<char>>;
-
-fn parse(input: &str) -> Grid {
-    input.lines().map(|l| l.chars().collect()).collect()
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Seat {
+    Floor,
+    Empty,
+    Occupied,
 }
 
-fn occupy_all_seats(grid: &mut Grid) {
-    for i in 0..grid.len() {
-        for j in 0..grid[0].len() {
-            if grid[i][j] == 'L' {
-                grid[i][j] = '#';
-            }
+impl Seat {
+    fn from_char(c: char) -> Result<Self, String> {
+        match c {
+            '.' => Ok(Seat::Floor),
+            'L' => Ok(Seat::Empty),
+            '#' => Ok(Seat::Occupied),
+            _ => Err(format!("unknown seat '{}'", c)),
         }
     }
 }
 
-fn count_adjacent_occupied_seats(grid: &[Vec<char>], pos: (i32, i32)) -> usize {
-    let (x, y) = pos;
+type Grid = Vec<Vec<Seat>>;
 
-    let adjacent_pos: Vec<(i32, i32)> = [
-        (x, y + 1),
-        (x + 1, y + 1),
-        (x + 1, y),
-        (x + 1, y - 1),
-        (x, y - 1),
-        (x - 1, y - 1),
-        (x - 1, y),
-        (x - 1, y + 1),
-    ]
-    .iter()
-    .filter(|pos| {
-        pos.0 >= 0 && pos.0 < grid.len() as i32 && pos.1 >= 0 && pos.1 < grid[0].len() as i32
-    })
-    .copied()
-    .collect();
-
-    adjacent_pos
-        .into_iter()
-        .filter(|pos| grid[pos.0 as usize][pos.1 as usize] == '#')
-        .count()
+fn parse(input: &str) -> Result<Grid, String> {
+    let grid: Grid = input
+        .lines()
+        .map(|l| l.chars().map(Seat::from_char).collect())
+        .collect::<Result<_, _>>()?;
+    if let Some(i) = grid.iter().position(|row| row.len() != grid[0].len()) {
+        return Err(format!(
+            "row {} has {} seats, expected {}",
+            i + 1,
+            grid[i].len(),
+            grid[0].len()
+        ));
+    }
+    Ok(grid)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Neighborhood {
+    // the eight surrounding positions
+    Adjacent,
+    // the first seat in each of the eight directions
+    LineOfSight,
+    // every position up to k steps away in any direction
+    Radius(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SeatRules {
+    neighborhood: Neighborhood,
+    // an empty seat becomes occupied if at most this many neighbours are occupied
+    sit_down: usize,
+    // an occupied seat becomes empty if at least this many neighbours are occupied
+    leave: usize,
 }
 
-fn count_visible_occupied_seats(grid: &[Vec<char>], pos: (i32, i32)) -> usize {
+impl SeatRules {
+    const PART1: SeatRules = SeatRules {
+        neighborhood: Neighborhood::Adjacent,
+        sit_down: 0,
+        leave: 4,
+    };
+    const PART2: SeatRules = SeatRules {
+        neighborhood: Neighborhood::LineOfSight,
+        sit_down: 0,
+        leave: 5,
+    };
+}
+
+const DIRECTIONS: [(i32, i32); 8] = [
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+];
+
+fn seat_at(grid: &[Vec<Seat>], x: i32, y: i32) -> Option<Seat> {
+    if x < 0 || y < 0 {
+        return None;
+    }
+    grid.get(x as usize)?.get(y as usize).copied()
+}
+
+fn count_occupied_neighbours(
+    grid: &[Vec<Seat>],
+    pos: (i32, i32),
+    neighborhood: Neighborhood,
+) -> usize {
     let (x, y) = pos;
-    let mut count = 0;
-
-    let directions = [(0, 1),
-        (1, 1),
-        (1, 0),
-        (1, -1),
-        (0, -1),
-        (-1, -1),
-        (-1, 0),
-        (-1, 1)];
-
-    for (dir_x, dir_y) in directions.iter() {
-        let mut xi = x + dir_x;
-        let mut yi = y + dir_y;
-        while (0..grid.len()).contains(&(xi as usize))
-            && (0..grid[0].len()).contains(&(yi as usize))
-        {
-            match grid[xi as usize][yi as usize] {
-                '#' => {
-                    count += 1;
-                    break;
+
+    match neighborhood {
+        Neighborhood::Adjacent => count_occupied_neighbours(grid, pos, Neighborhood::Radius(1)),
+        Neighborhood::Radius(k) => {
+            let k = k as i32;
+            (-k..=k)
+                .flat_map(|dx| (-k..=k).map(move |dy| (dx, dy)))
+                .filter(|&d| d != (0, 0))
+                .filter(|&(dx, dy)| seat_at(grid, x + dx, y + dy) == Some(Seat::Occupied))
+                .count()
+        }
+        Neighborhood::LineOfSight => DIRECTIONS
+            .iter()
+            .filter(|(dx, dy)| {
+                let (mut xi, mut yi) = (x + dx, y + dy);
+                while let Some(seat) = seat_at(grid, xi, yi) {
+                    match seat {
+                        Seat::Occupied => return true,
+                        Seat::Empty => return false,
+                        Seat::Floor => (),
+                    }
+                    xi += dx;
+                    yi += dy;
                 }
-                'L' => break,
-                _ => (),
+                false
+            })
+            .count(),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    // nothing changes anymore after the given number of rounds
+    FixedPoint(usize),
+    // from the given round on, the grid alternates between two states
+    Oscillation(usize),
+    // neither happened within the allowed number of rounds
+    Undecided,
+}
+
+// Seats flattened into arrays, with the neighbours of every seat computed once for
+// the chosen neighbourhood. Floor never changes, so neither do the neighbours.
+struct SeatGraph {
+    positions: Vec<(usize, usize)>,
+    // neighbours of seat i are neighbours[offsets[i]..offsets[i + 1]]
+    offsets: Vec<usize>,
+    neighbours: Vec<u32>,
+}
+
+impl SeatGraph {
+    fn new(grid: &[Vec<Seat>], neighborhood: Neighborhood) -> Self {
+        let height = grid.len() as i64;
+        let width = grid.first().map_or(0, Vec::len) as i64;
+
+        let mut index: Vec<Option<u32>> = vec![None; (width * height) as usize];
+        let mut positions = vec![];
+        for (i, row) in grid.iter().enumerate() {
+            for (j, &seat) in row.iter().enumerate() {
+                if seat != Seat::Floor {
+                    index[i * width as usize + j] = Some(positions.len() as u32);
+                    positions.push((i, j));
+                }
+            }
+        }
+        let inside = |x: i64, y: i64| (0..height).contains(&x) && (0..width).contains(&y);
+        let seat_index = |x: i64, y: i64| {
+            if inside(x, y) {
+                index[(x * width + y) as usize]
+            } else {
+                None
             }
+        };
 
-            xi += dir_x;
-            yi += dir_y;
+        let mut offsets = vec![0];
+        let mut neighbours = vec![];
+        for &(i, j) in positions.iter() {
+            let (x, y) = (i as i64, j as i64);
+            match neighborhood {
+                Neighborhood::Adjacent | Neighborhood::Radius(_) => {
+                    let k = match neighborhood {
+                        Neighborhood::Radius(k) => k as i64,
+                        _ => 1,
+                    };
+                    for dx in -k..=k {
+                        for dy in -k..=k {
+                            if (dx, dy) != (0, 0) {
+                                neighbours.extend(seat_index(x + dx, y + dy));
+                            }
+                        }
+                    }
+                }
+                Neighborhood::LineOfSight => {
+                    for &(dx, dy) in DIRECTIONS.iter() {
+                        let (dx, dy) = (dx as i64, dy as i64);
+                        let (mut xi, mut yi) = (x + dx, y + dy);
+                        while inside(xi, yi) {
+                            if let Some(n) = seat_index(xi, yi) {
+                                neighbours.push(n);
+                                break;
+                            }
+                            xi += dx;
+                            yi += dy;
+                        }
+                    }
+                }
+            }
+            offsets.push(neighbours.len());
         }
+
+        SeatGraph {
+            positions,
+            offsets,
+            neighbours,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    fn occupancy(&self, grid: &[Vec<Seat>]) -> Vec<bool> {
+        self.positions
+            .iter()
+            .map(|&(i, j)| grid[i][j] == Seat::Occupied)
+            .collect()
     }
 
-    count
+    fn write_back(&self, grid: &mut Grid, occupied: &[bool]) {
+        for (&(i, j), &o) in self.positions.iter().zip(occupied.iter()) {
+            grid[i][j] = if o { Seat::Occupied } else { Seat::Empty };
+        }
+    }
+
+    // computes the next round from `cur` into `next`, true if anything changed
+    fn step(&self, cur: &[bool], next: &mut [bool], rules: &SeatRules) -> bool {
+        let mut changed = false;
+
+        for (seat, n) in next.iter_mut().enumerate() {
+            let count = self.neighbours[self.offsets[seat]..self.offsets[seat + 1]]
+                .iter()
+                .filter(|&&other| cur[other as usize])
+                .count();
+            *n = if cur[seat] {
+                count < rules.leave
+            } else {
+                count <= rules.sit_down
+            };
+            changed |= *n != cur[seat];
+        }
+
+        changed
+    }
+}
+
+fn simulate(grid: &mut Grid, rules: &SeatRules, max_rounds: usize) -> Outcome {
+    run(grid, rules, max_rounds, None)
+}
+
+// Like simulate, but also returns every generation starting with the initial grid
+fn record(grid: &mut Grid, rules: &SeatRules, max_rounds: usize) -> (Outcome, Vec<Grid>) {
+    let mut frames = vec![grid.clone()];
+    let outcome = run(grid, rules, max_rounds, Some(&mut frames));
+    (outcome, frames)
 }
 
-fn simulate_once<F>(grid: &mut Grid, count_func: F, threshold: usize) -> bool
-where
-    F: Fn(&[Vec<char>], (i32, i32)) -> usize,
-{
+fn run(
+    grid: &mut Grid,
+    rules: &SeatRules,
+    max_rounds: usize,
+    mut frames: Option<&mut Vec<Grid>>,
+) -> Outcome {
+    let graph = SeatGraph::new(grid, rules.neighborhood);
+    let mut cur = graph.occupancy(grid);
+    let mut next = vec![false; graph.len()];
+    let mut before_last = vec![];
+    let mut outcome = Outcome::Undecided;
+
+    for round in 0..max_rounds {
+        let changed = graph.step(&cur, &mut next, rules);
+        mem::swap(&mut cur, &mut next);
+
+        if !changed {
+            outcome = Outcome::FixedPoint(round);
+            break;
+        }
+        if let Some(frames) = frames.as_mut() {
+            graph.write_back(grid, &cur);
+            frames.push(grid.clone());
+        }
+        if before_last == cur {
+            outcome = Outcome::Oscillation(round - 1);
+            break;
+        }
+        // reuse the buffers instead of allocating a new state every round
+        mem::swap(&mut before_last, &mut next);
+        next.resize(graph.len(), false);
+    }
+
+    graph.write_back(grid, &cur);
+    outcome
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::FixedPoint(rounds) => write!(f, "stable after {} rounds", rounds),
+            Outcome::Oscillation(round) => write!(f, "oscillating from round {} on", round),
+            Outcome::Undecided => write!(f, "not settled"),
+        }
+    }
+}
+
+fn seat_char(seat: Seat) -> char {
+    match seat {
+        Seat::Floor => '.',
+        Seat::Empty => 'L',
+        Seat::Occupied => '#',
+    }
+}
+
+// all frames as text, one block per generation
+fn frames_to_text(frames: &[Grid]) -> String {
+    let mut text = String::new();
+    for (round, grid) in frames.iter().enumerate() {
+        text += &format!("round {}: {} occupied\n", round, count_occupied_seats(grid));
+        for row in grid {
+            text.extend(row.iter().copied().map(seat_char));
+            text.push('\n');
+        }
+        text.push('\n');
+    }
+    text
+}
+
+// occupancy per generation, `changed` counts the seats that differ from the round before
+fn frames_to_csv(frames: &[Grid]) -> String {
+    let mut csv = String::from("round,occupied,empty,changed\n");
+    for (round, grid) in frames.iter().enumerate() {
+        let seats = grid.iter().flatten().filter(|&&s| s != Seat::Floor).count();
+        let occupied = count_occupied_seats(grid);
+        let changed = match round {
+            0 => 0,
+            _ => grid
+                .iter()
+                .flatten()
+                .zip(frames[round - 1].iter().flatten())
+                .filter(|(a, b)| a != b)
+                .count(),
+        };
+        csv += &format!("{},{},{},{}\n", round, occupied, seats - occupied, changed);
+    }
+    csv
+}
+
+// binary PPM image of a single generation, every seat drawn as a scale x scale square
+fn frame_to_ppm(grid: &[Vec<Seat>], scale: usize) -> Vec<u8> {
+    let height = grid.len() * scale;
+    let width = grid.first().map_or(0, Vec::len) * scale;
+
+    let mut image = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    for row in grid {
+        let mut line = Vec::with_capacity(width * 3);
+        for &seat in row {
+            let color: [u8; 3] = match seat {
+                Seat::Floor => [40, 40, 40],
+                Seat::Empty => [60, 180, 75],
+                Seat::Occupied => [230, 25, 75],
+            };
+            for _ in 0..scale {
+                line.extend_from_slice(&color);
+            }
+        }
+        for _ in 0..scale {
+            image.extend_from_slice(&line);
+        }
+    }
+    image
+}
+
+fn write_ppm_frames(dir: &Path, frames: &[Grid], scale: usize) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    for (round, grid) in frames.iter().enumerate() {
+        fs::write(
+            dir.join(format!("frame_{:04}.ppm", round)),
+            frame_to_ppm(grid, scale),
+        )?;
+    }
+    Ok(())
+}
+
+// Straightforward grid based simulation, kept as a reference for benchmarking
+fn simulate_once(grid: &mut Grid, rules: &SeatRules) -> bool {
     let cur_grid = grid.clone();
     let mut changed = false;
 
     for (i, row) in cur_grid.iter().enumerate() {
-        for (j, &c) in row.iter().enumerate() {
-            match c {
-                'L' if count_func(&cur_grid, (i as i32, j as i32)) == 0 => {
-                    grid[i][j] = '#';
+        for (j, &seat) in row.iter().enumerate() {
+            let count =
+                || count_occupied_neighbours(&cur_grid, (i as i32, j as i32), rules.neighborhood);
+            match seat {
+                Seat::Empty if count() <= rules.sit_down => {
+                    grid[i][j] = Seat::Occupied;
                     changed = true;
                 }
-                '#' if count_func(&cur_grid, (i as i32, j as i32)) >= threshold => {
-                    grid[i][j] = 'L';
+                Seat::Occupied if count() >= rules.leave => {
+                    grid[i][j] = Seat::Empty;
                     changed = true;
                 }
-                c => grid[i][j] = c,
+                _ => (),
             }
         }
     }
@@ -104,41 +414,138 @@ where
     changed
 }
 
-fn count_occupied_seats(grid: &[Vec<char>]) -> usize {
-    grid.iter().flatten().filter(|&c| *c == '#').count()
+fn simulate_naive(grid: &mut Grid, rules: &SeatRules) {
+    while simulate_once(grid, rules) {}
+}
+
+fn count_occupied_seats(grid: &[Vec<Seat>]) -> usize {
+    grid.iter()
+        .flatten()
+        .filter(|&s| *s == Seat::Occupied)
+        .count()
 }
 
 struct Solution;
 
 impl Solution {
     fn part1(grid: &mut Grid) -> usize {
-        loop {
-            if !simulate_once(grid, count_adjacent_occupied_seats, 4) {
-                break;
-            }
-        }
+        simulate(grid, &SeatRules::PART1, usize::MAX);
         count_occupied_seats(grid)
     }
 
     fn part2(grid: &mut Grid) -> usize {
-        loop {
-            if !simulate_once(grid, count_visible_occupied_seats, 5) {
-                break;
-            }
-        }
+        simulate(grid, &SeatRules::PART2, usize::MAX);
         count_occupied_seats(grid)
     }
 }
 
+fn parse_rules(args: &[String]) -> Result<Option<SeatRules>, String> {
+    let value = |name: &str| args.iter().position(|a| a == name).map(|i| args.get(i + 1));
+    let number = |name: &str| -> Result<Option<usize>, String> {
+        match value(name) {
+            Some(Some(n)) => n
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("invalid {} '{}'", name, n)),
+            Some(None) => Err(format!("missing value for {}", name)),
+            None => Ok(None),
+        }
+    };
+
+    let neighborhood = match value("--neighborhood") {
+        None => return Ok(None),
+        Some(Some(n)) if n == "adjacent" => Neighborhood::Adjacent,
+        Some(Some(n)) if n == "sight" => Neighborhood::LineOfSight,
+        Some(Some(n)) if n.starts_with("radius:") => Neighborhood::Radius(
+            n["radius:".len()..]
+                .parse()
+                .map_err(|_| format!("invalid radius '{}'", n))?,
+        ),
+        Some(n) => return Err(format!("unknown neighborhood {:?}", n)),
+    };
+
+    Ok(Some(SeatRules {
+        neighborhood,
+        sit_down: number("--sit")?.unwrap_or(0),
+        leave: number("--leave")?.unwrap_or(4),
+    }))
+}
+
+fn write_or_print(file: Option<&str>, content: String) -> io::Result<()> {
+    match file {
+        Some(file) => fs::write(file, content),
+        None => {
+            print!("{}", content);
+            Ok(())
+        }
+    }
+}
+
 fn main() {
     let input = fs::read_to_string("./input/day11.txt").expect("File not found!");
-    let mut grid = parse(&input);
+    let mut grid = parse(&input).unwrap_or_else(|e| panic!("{}", e));
 
-    occupy_all_seats(&mut grid);
-    let mut grid_p2 = grid.clone();
+    // usage: day11 [--bench | --neighborhood adjacent|sight|radius:<k> [--sit <n>] [--leave <n>]]
+    //               [--record text|csv|ppm [--out <file or directory>]]
+    let args: Vec<String> = env::args().skip(1).collect();
+    let rules = parse_rules(&args).unwrap_or_else(|e| panic!("{}", e));
+    let option = |name: &str| {
+        args.iter()
+            .position(|a| a == name)
+            .map(|i| args.get(i + 1).expect("missing option value").as_str())
+    };
 
-    println!("p1: {}", Solution::part1(&mut grid));
-    println!("p2: {}", Solution::part2(&mut grid_p2));
+    if let Some(format) = option("--record") {
+        let (outcome, frames) = record(&mut grid, &rules.unwrap_or(SeatRules::PART1), 10_000);
+        let out = option("--out");
+
+        let written = match (format, out) {
+            ("text", _) => write_or_print(out, frames_to_text(&frames)),
+            ("csv", _) => write_or_print(out, frames_to_csv(&frames)),
+            ("ppm", Some(dir)) => write_ppm_frames(Path::new(dir), &frames, 4),
+            ("ppm", None) => panic!("ppm frames need an output directory"),
+            _ => panic!("unknown record format '{}'", format),
+        };
+        written.expect("Failed to write frames!");
+
+        println!("{} ({} frames)", outcome, frames.len());
+        return;
+    }
+
+    match rules {
+        Some(rules) => {
+            println!("{}", simulate(&mut grid, &rules, 10_000));
+            println!("occupied seats: {}", count_occupied_seats(&grid));
+        }
+        None if args.iter().any(|a| a == "--bench") => {
+            for (part, rules) in [("p1", SeatRules::PART1), ("p2", SeatRules::PART2)].iter() {
+                let mut naive_grid = grid.clone();
+                let timer = Instant::now();
+                simulate_naive(&mut naive_grid, rules);
+                let naive = timer.elapsed();
+
+                let mut fast_grid = grid.clone();
+                let timer = Instant::now();
+                simulate(&mut fast_grid, rules, usize::MAX);
+                let fast = timer.elapsed();
+
+                assert!(naive_grid == fast_grid);
+                println!(
+                    "{}: {} (naive: {:?}, precomputed: {:?})",
+                    part,
+                    count_occupied_seats(&fast_grid),
+                    naive,
+                    fast
+                );
+            }
+        }
+        None => {
+            let mut grid_p2 = grid.clone();
+
+            println!("p1: {}", Solution::part1(&mut grid));
+            println!("p2: {}", Solution::part2(&mut grid_p2));
+        }
+    }
 }
 
 #[cfg(test)]
@@ -158,11 +565,100 @@ L.LLLLL.LL
 LLLLLLLLLL
 L.LLLLLL.L
 L.LLLLL.LL";
-        let mut grid = parse(input1);
-        occupy_all_seats(&mut grid);
+        let mut grid = parse(input1).unwrap();
         let mut grid_p2 = grid.clone();
 
         assert_eq!(Solution::part1(&mut grid), 37);
         assert_eq!(Solution::part2(&mut grid_p2), 26);
     }
+
+    #[test]
+    fn test_day11_outcomes() {
+        let grid = parse("L.LL.LL.LL\nLLLLLLL.LL\nL.L.L..L..").unwrap();
+        assert_eq!(
+            simulate(&mut grid.clone(), &SeatRules::PART1, 100),
+            Outcome::FixedPoint(3)
+        );
+
+        // two seats that can't stand each other sit down and leave in turns
+        let rules = SeatRules {
+            neighborhood: Neighborhood::Adjacent,
+            sit_down: 0,
+            leave: 1,
+        };
+        assert_eq!(
+            simulate(&mut parse("LL").unwrap(), &rules, 100),
+            Outcome::Oscillation(0)
+        );
+
+        let rules = SeatRules {
+            neighborhood: Neighborhood::Radius(2),
+            sit_down: 0,
+            leave: 1,
+        };
+        let mut grid = parse("L.L.L").unwrap();
+        assert_eq!(
+            count_occupied_neighbours(&parse("#.#.#").unwrap(), (0, 2), rules.neighborhood),
+            2
+        );
+        assert_eq!(simulate(&mut grid, &rules, 100), Outcome::Oscillation(0));
+        assert!(parse("L.X").is_err());
+        assert!(parse("LL\nLLL").is_err());
+        assert!(parse("LLL\nLL").is_err());
+    }
+
+    #[test]
+    fn test_day11_record() {
+        let mut grid = parse("L.L\nL.L").unwrap();
+        let (outcome, frames) = record(&mut grid, &SeatRules::PART1, 100);
+
+        assert_eq!(outcome, Outcome::FixedPoint(1));
+        assert_eq!(frames.len(), 2);
+        assert_eq!(
+            frames_to_text(&frames),
+            "round 0: 0 occupied\nL.L\nL.L\n\nround 1: 4 occupied\n#.#\n#.#\n\n"
+        );
+        assert_eq!(
+            frames_to_csv(&frames),
+            "round,occupied,empty,changed\n0,0,4,0\n1,4,0,4\n"
+        );
+
+        let ppm = frame_to_ppm(&frames[1], 2);
+        assert!(ppm.starts_with(b"P6\n6 4\n255\n"));
+        assert_eq!(ppm.len(), "P6\n6 4\n255\n".len() + 6 * 4 * 3);
+    }
+
+    #[test]
+    fn test_day11_matches_naive() {
+        let grid = parse(
+            "\
+L.LL.LL.LL
+LLLLLLL.LL
+L.L.L..L..
+LLLL.LL.LL
+L.LL.LL.LL",
+        )
+        .unwrap();
+
+        for neighborhood in [
+            Neighborhood::Adjacent,
+            Neighborhood::LineOfSight,
+            Neighborhood::Radius(2),
+        ]
+        .iter()
+        {
+            let rules = SeatRules {
+                neighborhood: *neighborhood,
+                sit_down: 1,
+                leave: 4,
+            };
+            let mut expected = grid.clone();
+            let mut actual = grid.clone();
+            for _ in 0..5 {
+                simulate_once(&mut expected, &rules);
+                simulate(&mut actual, &rules, 1);
+                assert_eq!(actual, expected);
+            }
+        }
+    }
 }
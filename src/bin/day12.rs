@@ -1,4 +1,8 @@
+use std::env;
+use std::fmt;
 use std::fs;
+use std::iter::once;
+use std::ops::{Add, Mul, Neg, Sub};
 use std::time::Instant;
 
 fn parse(input: &str) -> Vec<Instruction> {
@@ -22,7 +26,7 @@ fn parse(input: &str) -> Vec<Instruction> {
         .collect()
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum Instruction {
     North(i32),
     South(i32),
@@ -33,34 +37,179 @@ enum Instruction {
     Forward(i32),
 }
 
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instruction::North(v) => write!(f, "N{}", v),
+            Instruction::South(v) => write!(f, "S{}", v),
+            Instruction::East(v) => write!(f, "E{}", v),
+            Instruction::West(v) => write!(f, "W{}", v),
+            Instruction::Left(v) => write!(f, "L{}", v),
+            Instruction::Right(v) => write!(f, "R{}", v),
+            Instruction::Forward(v) => write!(f, "F{}", v),
+        }
+    }
+}
+
+// Fixed-point number with six decimal places. Coordinates stay exact as long as the
+// ship only turns by multiples of 90 degrees, any other angle is rounded to the
+// nearest millionth after each rotation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+struct Fixed(i64);
+
+impl Fixed {
+    const SCALE: i64 = 1_000_000;
+
+    fn from_int(value: i64) -> Self {
+        Fixed(value * Self::SCALE)
+    }
+
+    fn from_f64(value: f64) -> Self {
+        Fixed((value * Self::SCALE as f64).round() as i64)
+    }
+
+    fn to_f64(self) -> f64 {
+        self.0 as f64 / Self::SCALE as f64
+    }
+
+    // nearest integer, halves away from zero
+    fn round(self) -> i64 {
+        let half = Self::SCALE / 2 * self.0.signum();
+        (self.0 + half) / Self::SCALE
+    }
+
+    fn abs(self) -> Self {
+        Fixed(self.0.abs())
+    }
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+
+    fn add(self, other: Fixed) -> Fixed {
+        Fixed(self.0 + other.0)
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+
+    fn sub(self, other: Fixed) -> Fixed {
+        Fixed(self.0 - other.0)
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Fixed;
+
+    fn neg(self) -> Fixed {
+        Fixed(-self.0)
+    }
+}
+
+impl Mul<i64> for Fixed {
+    type Output = Fixed;
+
+    fn mul(self, factor: i64) -> Fixed {
+        Fixed(self.0 * factor)
+    }
+}
+
+impl fmt::Display for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let (int, frac) = (self.0.abs() / Self::SCALE, self.0.abs() % Self::SCALE);
+        if frac == 0 {
+            write!(f, "{}{}", sign, int)
+        } else {
+            let frac = format!("{:06}", frac);
+            write!(f, "{}{}.{}", sign, int, frac.trim_end_matches('0'))
+        }
+    }
+}
+
+// (east, north)
+type Point = (Fixed, Fixed);
+
+fn point(east: i64, north: i64) -> Point {
+    (Fixed::from_int(east), Fixed::from_int(north))
+}
+
+fn manhattan(p: Point) -> Fixed {
+    p.0.abs() + p.1.abs()
+}
+
+// Rotates p clockwise around the origin. Quarter turns are done exactly.
+fn rotate(p: Point, degrees: i32) -> Point {
+    let (x, y) = p;
+    match degrees.rem_euclid(360) {
+        0 => (x, y),
+        90 => (y, -x),
+        180 => (-x, -y),
+        270 => (-y, x),
+        d => {
+            let (sin, cos) = (d as f64).to_radians().sin_cos();
+            let (x, y) = (x.to_f64(), y.to_f64());
+            (
+                Fixed::from_f64(x * cos + y * sin),
+                Fixed::from_f64(-x * sin + y * cos),
+            )
+        }
+    }
+}
+
+// state of the ship after an instruction
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Step {
+    position: Point,
+    waypoint: Point,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Trajectory {
+    steps: Vec<Step>,
+    min: Point,
+    max: Point,
+    // largest Manhattan distance from the start
+    max_distance: Fixed,
+    // length of the path actually sailed
+    total_distance: f64,
+}
+
 #[derive(Clone, Debug)]
 struct Ferry {
-    position: (i32, i32),
-    facing: i32, // 0: North, 90: East, 180: South, 270: West
-    waypoint: (i32, i32),
+    position: Point,
+    facing: i32, // clockwise from North: 0: North, 90: East, 180: South, 270: West
+    waypoint: Point,
+    track: Vec<Step>,
 }
 
 impl Ferry {
     fn new() -> Self {
-        Self {
-            position: (0, 0),
+        let mut ferry = Self {
+            position: point(0, 0),
             facing: 90,
-            waypoint: (10, 1),
-        }
+            waypoint: point(10, 1),
+            track: vec![],
+        };
+        ferry.record();
+        ferry
+    }
+
+    fn record(&mut self) {
+        self.track.push(Step {
+            position: self.position,
+            waypoint: self.waypoint,
+        });
     }
 
-    fn dist_from_start(&self) -> i32 {
-        self.position.0.abs() + self.position.1.abs()
+    fn dist_from_start(&self) -> i64 {
+        manhattan(self.position).round()
     }
 
     fn move_forward(&mut self, dist: i32) {
-        match self.facing {
-            0 => self.position.1 += dist,
-            90 => self.position.0 += dist,
-            180 => self.position.1 -= dist,
-            270 => self.position.0 -= dist,
-            _ => panic!("unknown current facing: {}", self.facing),
-        }
+        let (dx, dy) = rotate(point(0, dist as i64), self.facing);
+        self.position = (self.position.0 + dx, self.position.1 + dy);
     }
 
     fn turn(&mut self, degrees: i32) {
@@ -69,56 +218,558 @@ impl Ferry {
 
     fn process_instructions_immediate(&mut self, instructions: &[Instruction]) {
         for &instr in instructions {
+            let (x, y) = &mut self.position;
             match instr {
-                Instruction::North(dist) => self.position.1 += dist,
-                Instruction::South(dist) => self.position.1 -= dist,
-                Instruction::East(dist) => self.position.0 += dist,
-                Instruction::West(dist) => self.position.0 -= dist,
+                Instruction::North(dist) => *y = *y + Fixed::from_int(dist as i64),
+                Instruction::South(dist) => *y = *y - Fixed::from_int(dist as i64),
+                Instruction::East(dist) => *x = *x + Fixed::from_int(dist as i64),
+                Instruction::West(dist) => *x = *x - Fixed::from_int(dist as i64),
                 Instruction::Left(degrees) => self.turn(-degrees),
                 Instruction::Right(degrees) => self.turn(degrees),
                 Instruction::Forward(dist) => self.move_forward(dist),
             }
+            self.record();
         }
     }
 
     fn rotate_waypoint(&mut self, degrees: i32) {
-        match degrees.rem_euclid(360) {
-            90 => self.waypoint = (self.waypoint.1, -self.waypoint.0),
-            180 => self.waypoint = (-self.waypoint.0, -self.waypoint.1),
-            270 => self.waypoint = (-self.waypoint.1, self.waypoint.0),
-            _ => panic!("unknown waypoint rotation by {} degrees", degrees),
-        }
+        self.waypoint = rotate(self.waypoint, degrees);
     }
 
     fn move_towards_waypoint(&mut self, times: i32) {
-        self.position.0 += times * self.waypoint.0;
-        self.position.1 += times * self.waypoint.1;
+        self.position.0 = self.position.0 + self.waypoint.0 * times as i64;
+        self.position.1 = self.position.1 + self.waypoint.1 * times as i64;
     }
 
     fn process_instructions_relative(&mut self, instructions: &[Instruction]) {
         for &instr in instructions {
+            let (x, y) = &mut self.waypoint;
             match instr {
-                Instruction::North(value) => self.waypoint.1 += value,
-                Instruction::South(value) => self.waypoint.1 -= value,
-                Instruction::East(value) => self.waypoint.0 += value,
-                Instruction::West(value) => self.waypoint.0 -= value,
+                Instruction::North(value) => *y = *y + Fixed::from_int(value as i64),
+                Instruction::South(value) => *y = *y - Fixed::from_int(value as i64),
+                Instruction::East(value) => *x = *x + Fixed::from_int(value as i64),
+                Instruction::West(value) => *x = *x - Fixed::from_int(value as i64),
                 Instruction::Left(degrees) => self.rotate_waypoint(-degrees),
                 Instruction::Right(degrees) => self.rotate_waypoint(degrees),
                 Instruction::Forward(times) => self.move_towards_waypoint(times),
             }
+            self.record();
+        }
+    }
+
+    fn trajectory(&self) -> Trajectory {
+        let start = self.track[0].position;
+        let positions = || self.track.iter().map(|s| s.position);
+
+        let min = positions().fold(start, |m, p| (m.0.min(p.0), m.1.min(p.1)));
+        let max = positions().fold(start, |m, p| (m.0.max(p.0), m.1.max(p.1)));
+        let max_distance = positions()
+            .map(|p| manhattan((p.0 - start.0, p.1 - start.1)))
+            .max()
+            .unwrap_or_default();
+        let total_distance = self
+            .track
+            .windows(2)
+            .map(|w| {
+                let dx = (w[1].position.0 - w[0].position.0).to_f64();
+                let dy = (w[1].position.1 - w[0].position.1).to_f64();
+                dx.hypot(dy)
+            })
+            .sum();
+
+        Trajectory {
+            steps: self.track.clone(),
+            min,
+            max,
+            max_distance,
+            total_distance,
+        }
+    }
+}
+
+fn print_trajectory(name: &str, trajectory: &Trajectory) {
+    let (min, max) = (trajectory.min, trajectory.max);
+    println!(
+        "{}: {} steps, bounding box ({}, {})..({}, {}), max distance {}, travelled {:.3}",
+        name,
+        trajectory.steps.len() - 1,
+        min.0,
+        min.1,
+        max.0,
+        max.1,
+        trajectory.max_distance,
+        trajectory.total_distance
+    );
+}
+
+// Renders both routes into one SVG image, north pointing up. The immediate route is
+// drawn in blue and the waypoint route in orange, the waypoint vector of every step
+// as a thin grey line starting at the ship.
+fn to_svg(immediate: &Trajectory, waypoint: &Trajectory) -> String {
+    let min_x = immediate.min.0.min(waypoint.min.0).to_f64();
+    let min_y = immediate.min.1.min(waypoint.min.1).to_f64();
+    let max_x = immediate.max.0.max(waypoint.max.0).to_f64();
+    let max_y = immediate.max.1.max(waypoint.max.1).to_f64();
+    let extent = (max_x - min_x).max(max_y - min_y).max(1.0);
+    let margin = extent / 20.0;
+    let stroke = extent / 500.0;
+
+    // largest power of ten not exceeding a fifth of the image
+    let scale = 10f64.powi((extent / 5.0).log10().floor() as i32);
+    let (width, height) = (max_x - min_x + 2.0 * margin, max_y - min_y + 2.0 * margin);
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n",
+        min_x - margin,
+        -max_y - margin,
+        width,
+        height
+    );
+    svg += &format!(
+        "<g stroke-width=\"{}\" fill=\"none\" stroke-linejoin=\"round\">\n",
+        stroke
+    );
+
+    let coords = |p: Point| format!("{},{}", p.0.to_f64(), (-p.1).to_f64());
+    for step in &waypoint.steps {
+        let tip = (
+            step.position.0 + step.waypoint.0,
+            step.position.1 + step.waypoint.1,
+        );
+        svg += &format!(
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"#bbb\" stroke-width=\"{}\"/>\n",
+            step.position.0.to_f64(),
+            (-step.position.1).to_f64(),
+            tip.0.to_f64(),
+            (-tip.1).to_f64(),
+            stroke / 2.0
+        );
+    }
+    for (trajectory, color) in &[(immediate, "#1f77b4"), (waypoint, "#ff7f0e")] {
+        let points: Vec<String> = trajectory
+            .steps
+            .iter()
+            .map(|s| coords(s.position))
+            .collect();
+        svg += &format!(
+            "<polyline points=\"{}\" stroke=\"{}\"/>\n",
+            points.join(" "),
+            color
+        );
+        if let (Some(first), Some(last)) = (trajectory.steps.first(), trajectory.steps.last()) {
+            for (p, marker) in &[(first.position, "green"), (last.position, "red")] {
+                svg += &format!(
+                    "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\" stroke=\"{}\"/>\n",
+                    p.0.to_f64(),
+                    (-p.1).to_f64(),
+                    stroke * 4.0,
+                    marker,
+                    color
+                );
+            }
+        }
+    }
+    svg += "</g>\n";
+
+    // scale bar in the lower left corner
+    let (x, y) = (min_x - margin / 2.0, -min_y + margin / 2.0);
+    svg += &format!(
+        "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\" stroke-width=\"{}\"/>\n",
+        x,
+        y,
+        x + scale,
+        y,
+        stroke
+    );
+    svg += &format!(
+        "<text x=\"{}\" y=\"{}\" font-size=\"{}\">{} units</text>\n",
+        x,
+        y - stroke * 3.0,
+        margin / 3.0,
+        scale
+    );
+    svg += "</svg>\n";
+    svg
+}
+
+// Cost of a single instruction by kind, independent of its value
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Costs {
+    shift: u32,
+    turn: u32,
+    forward: u32,
+}
+
+impl Default for Costs {
+    fn default() -> Self {
+        Costs {
+            shift: 1,
+            turn: 1,
+            forward: 1,
+        }
+    }
+}
+
+impl Costs {
+    fn of(&self, instr: &Instruction) -> u32 {
+        match instr {
+            Instruction::North(_)
+            | Instruction::South(_)
+            | Instruction::East(_)
+            | Instruction::West(_) => self.shift,
+            Instruction::Left(_) | Instruction::Right(_) => self.turn,
+            Instruction::Forward(_) => self.forward,
+        }
+    }
+
+    fn total(&self, plan: &[Instruction]) -> u32 {
+        plan.iter().map(|i| self.of(i)).sum()
+    }
+
+    // cheaper plans first, shorter ones among equally expensive plans
+    fn better(&self, a: &[Instruction], b: &[Instruction]) -> bool {
+        (self.total(a), a.len()) < (self.total(b), b.len())
+    }
+}
+
+fn integer_point(p: Point) -> Result<(i32, i32), String> {
+    if p.0 .0 % Fixed::SCALE != 0 || p.1 .0 % Fixed::SCALE != 0 {
+        return Err(format!("({}, {}) is not on the integer grid", p.0, p.1));
+    }
+    Ok((p.0.round() as i32, p.1.round() as i32))
+}
+
+fn turn_towards(from: i32, to: i32) -> Instruction {
+    match (to - from).rem_euclid(360) {
+        d if d <= 180 => Instruction::Right(d),
+        d => Instruction::Left(360 - d),
+    }
+}
+
+// shifts moving (east, north) by the given offset
+fn shifts(dx: i32, dy: i32) -> Vec<Instruction> {
+    let mut plan = vec![];
+    match dx {
+        0 => (),
+        d if d > 0 => plan.push(Instruction::East(d)),
+        d => plan.push(Instruction::West(-d)),
+    }
+    match dy {
+        0 => (),
+        d if d > 0 => plan.push(Instruction::North(d)),
+        d => plan.push(Instruction::South(-d)),
+    }
+    plan
+}
+
+// Re-runs a plan and checks that it takes the ship where it was meant to go
+fn verify(
+    start: &Ferry,
+    plan: &[Instruction],
+    relative: bool,
+    check: impl Fn(&Ferry) -> bool,
+) -> Result<(), String> {
+    let mut ferry = start.clone();
+    if relative {
+        ferry.process_instructions_relative(plan);
+    } else {
+        ferry.process_instructions_immediate(plan);
+    }
+    if check(&ferry) {
+        Ok(())
+    } else {
+        let plan: Vec<String> = plan.iter().map(Instruction::to_string).collect();
+        Err(format!("plan {} misses its target", plan.join(" ")))
+    }
+}
+
+// Cheapest instruction sequence taking the ship from `start` to `target` and leaving
+// it facing `heading` when interpreted by process_instructions_immediate. Each axis
+// of the offset is covered either by a shift or by moving forward along it, so apart
+// from the final heading only quarter headings are worth turning to.
+fn plan_immediate(
+    start: &Ferry,
+    target: (i32, i32),
+    heading: i32,
+    costs: &Costs,
+) -> Result<Vec<Instruction>, String> {
+    let from = integer_point(start.position)?;
+    let (dx, dy) = (target.0 - from.0, target.1 - from.1);
+    let heading = heading.rem_euclid(360);
+
+    // the shift and the heading that cover each remaining axis
+    let mut axes = vec![];
+    if dx != 0 {
+        axes.push((shifts(dx, 0)[0], if dx > 0 { 90 } else { 270 }, dx.abs()));
+    }
+    if dy != 0 {
+        axes.push((shifts(0, dy)[0], if dy > 0 { 0 } else { 180 }, dy.abs()));
+    }
+
+    fn search(
+        facing: i32,
+        todo: &[(Instruction, i32, i32)],
+        heading: i32,
+        plan: &mut Vec<Instruction>,
+        costs: &Costs,
+        best: &mut Option<Vec<Instruction>>,
+    ) {
+        if best.as_ref().is_some_and(|b| !costs.better(plan, b)) {
+            return;
+        }
+        if todo.is_empty() {
+            if facing != heading {
+                plan.push(turn_towards(facing, heading));
+            }
+            if best.as_ref().is_none_or(|b| costs.better(plan, b)) {
+                *best = Some(plan.clone());
+            }
+            if facing != heading {
+                plan.pop();
+            }
+            return;
+        }
+
+        for &(shift, direction, dist) in todo {
+            let rest: Vec<_> = todo.iter().copied().filter(|&(s, ..)| s != shift).collect();
+            let forward = if facing == direction {
+                vec![Instruction::Forward(dist)]
+            } else {
+                vec![turn_towards(facing, direction), Instruction::Forward(dist)]
+            };
+
+            for (step, next) in [(forward, direction), (vec![shift], facing)] {
+                plan.extend(&step);
+                search(next, &rest, heading, plan, costs, best);
+                plan.truncate(plan.len() - step.len());
+            }
+        }
+    }
+
+    let mut best = None;
+    search(start.facing, &axes, heading, &mut vec![], costs, &mut best);
+    let plan = best.unwrap_or_default();
+
+    verify(start, &plan, false, |f| {
+        f.position == point(target.0 as i64, target.1 as i64) && f.facing == heading
+    })?;
+    Ok(plan)
+}
+
+// Cheapest way to turn waypoint a into waypoint b using shifts and at most one
+// quarter turn, before or after the shifts.
+fn reshape_waypoint(a: (i32, i32), b: (i32, i32), costs: &Costs) -> Vec<Instruction> {
+    let quarter = |p: (i32, i32), degrees: i32| {
+        let (x, y) = rotate(point(p.0 as i64, p.1 as i64), degrees);
+        (x.round() as i32, y.round() as i32)
+    };
+
+    let mut best = shifts(b.0 - a.0, b.1 - a.1);
+    for degrees in [90, 180, 270] {
+        let turn = turn_towards(0, degrees);
+
+        let turned = quarter(a, degrees);
+        let mut plan = vec![turn];
+        plan.extend(shifts(b.0 - turned.0, b.1 - turned.1));
+        if costs.better(&plan, &best) {
+            best = plan;
+        }
+
+        let before = quarter(b, -degrees);
+        let mut plan = shifts(before.0 - a.0, before.1 - a.1);
+        plan.push(turn);
+        if costs.better(&plan, &best) {
+            best = plan;
+        }
+    }
+    best
+}
+
+fn divisors(n: i32) -> Vec<i32> {
+    let mut divs = vec![];
+    let mut d = 1;
+    while d * d <= n {
+        if n % d == 0 {
+            divs.push(d);
+            if d != n / d {
+                divs.push(n / d);
+            }
+        }
+        d += 1;
+    }
+    divs.sort_unstable();
+    divs
+}
+
+fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+// Waypoints worth sailing the next leg towards: the current one turned by a quarter
+// turn with at most one coordinate replaced by a share of the remaining offset along
+// that axis or by the final waypoint's, and every even share of the whole offset.
+fn leg_waypoints(w: (i32, i32), offset: (i32, i32), last: (i32, i32)) -> Vec<(i32, i32)> {
+    let shares = |d: i32| -> Vec<i32> {
+        if d == 0 {
+            vec![0]
+        } else {
+            divisors(d.abs()).into_iter().map(|t| d / t).collect()
+        }
+    };
+
+    let mut legs = vec![];
+    let mut turned = w;
+    for _ in 0..4 {
+        legs.push(turned);
+        for x in shares(offset.0).into_iter().chain(once(last.0)) {
+            legs.push((x, turned.1));
+        }
+        for y in shares(offset.1).into_iter().chain(once(last.1)) {
+            legs.push((turned.0, y));
+        }
+        turned = (turned.1, -turned.0);
+    }
+    let g = gcd(offset.0, offset.1);
+    if g != 0 {
+        legs.extend(
+            divisors(g)
+                .into_iter()
+                .map(|t| (offset.0 / t, offset.1 / t)),
+        );
+    }
+    legs.sort_unstable();
+    legs.dedup();
+    legs
+}
+
+// Moving forward once, or as often as it takes to line the ship up with the target
+// on one of the axes.
+fn forward_counts(w: (i32, i32), offset: (i32, i32)) -> Vec<i32> {
+    let mut counts = vec![1];
+    for (d, step) in [(offset.0, w.0), (offset.1, w.1)] {
+        if step != 0 && d % step == 0 && d / step > 1 {
+            counts.push(d / step);
+        }
+    }
+    counts.sort_unstable();
+    counts.dedup();
+    counts
+}
+
+// Cheapest instruction sequence taking the ship from `start` to `target` with the
+// waypoint ending up at `waypoint` when interpreted by process_instructions_relative.
+// Plans sail in up to MAX_LEGS legs, each reshaping the waypoint into one of
+// leg_waypoints and moving forward by one of forward_counts, and end by reshaping the
+// waypoint into the final one. A depth-first search finds the cheapest of those plans,
+// cutting off partial plans that can't beat the best one found so far.
+fn plan_relative(
+    start: &Ferry,
+    target: (i32, i32),
+    waypoint: (i32, i32),
+    costs: &Costs,
+) -> Result<Vec<Instruction>, String> {
+    const MAX_LEGS: usize = 4;
+
+    let from = integer_point(start.position)?;
+    let start_waypoint = integer_point(start.waypoint)?;
+
+    fn search(
+        at: (i32, i32),
+        w: (i32, i32),
+        goal: ((i32, i32), (i32, i32)),
+        legs: usize,
+        plan: &mut Vec<Instruction>,
+        costs: &Costs,
+        best: &mut Option<Vec<Instruction>>,
+    ) {
+        let (target, last) = goal;
+        // away from the target the plan needs at least one more forward instruction
+        let rest = if at == target {
+            (0, 0)
+        } else {
+            (costs.forward, 1)
+        };
+        let bound = (costs.total(plan) + rest.0, plan.len() + rest.1);
+        if best
+            .as_ref()
+            .is_some_and(|b| bound >= (costs.total(b), b.len()))
+        {
+            return;
+        }
+
+        if at == target {
+            let finish = reshape_waypoint(w, last, costs);
+            plan.extend(&finish);
+            if best.as_ref().is_none_or(|b| costs.better(plan, b)) {
+                *best = Some(plan.clone());
+            }
+            plan.truncate(plan.len() - finish.len());
+        }
+        if legs == MAX_LEGS {
+            return;
         }
+
+        let offset = (target.0 - at.0, target.1 - at.1);
+        for leg in leg_waypoints(w, offset, last) {
+            let reshape = reshape_waypoint(w, leg, costs);
+            for times in forward_counts(leg, offset) {
+                let next = match (
+                    times.checked_mul(leg.0).and_then(|dx| at.0.checked_add(dx)),
+                    times.checked_mul(leg.1).and_then(|dy| at.1.checked_add(dy)),
+                ) {
+                    (Some(x), Some(y)) => (x, y),
+                    _ => continue,
+                };
+                plan.extend(&reshape);
+                plan.push(Instruction::Forward(times));
+                search(next, leg, goal, legs + 1, plan, costs, best);
+                plan.truncate(plan.len() - reshape.len() - 1);
+            }
+        }
+    }
+
+    // sailing straight to the target in one leg bounds the search from the start
+    let offset = (target.0 - from.0, target.1 - from.1);
+    let (mut direct, mut w) = (vec![], start_waypoint);
+    if offset != (0, 0) {
+        direct = reshape_waypoint(w, offset, costs);
+        direct.push(Instruction::Forward(1));
+        w = offset;
     }
+    direct.extend(reshape_waypoint(w, waypoint, costs));
+
+    let mut best = Some(direct);
+    search(
+        from,
+        start_waypoint,
+        (target, waypoint),
+        0,
+        &mut vec![],
+        costs,
+        &mut best,
+    );
+    let plan = best.unwrap_or_default();
+
+    verify(start, &plan, true, |f| {
+        f.position == point(target.0 as i64, target.1 as i64)
+            && f.waypoint == point(waypoint.0 as i64, waypoint.1 as i64)
+    })?;
+    Ok(plan)
 }
 
 struct Solution;
 
 impl Solution {
-    fn part1(ferry: &mut Ferry, instructions: &[Instruction]) -> i32 {
+    fn part1(ferry: &mut Ferry, instructions: &[Instruction]) -> i64 {
         ferry.process_instructions_immediate(instructions);
         ferry.dist_from_start()
     }
 
-    fn part2(ferry: &mut Ferry, instructions: &[Instruction]) -> i32 {
+    fn part2(ferry: &mut Ferry, instructions: &[Instruction]) -> i64 {
         ferry.process_instructions_relative(instructions);
         ferry.dist_from_start()
     }
@@ -144,6 +795,55 @@ fn main() {
         Solution::part2(&mut ferry2, &instructions),
         timer.elapsed()
     );
+
+    // usage: day12 [--trajectory] [--svg <file>] [--costs <shift>,<turn>,<forward>]
+    //             [--plan <x>,<y>,<heading>] [--plan-waypoint <x>,<y>,<wx>,<wy>]
+    let args: Vec<String> = env::args().skip(1).collect();
+    let numbers = |name: &str, count: usize| -> Option<Vec<i32>> {
+        let i = args.iter().position(|a| a == name)?;
+        let values: Vec<i32> = args
+            .get(i + 1)
+            .map(|v| v.split(',').flat_map(str::parse).collect())
+            .unwrap_or_default();
+        if values.len() != count {
+            panic!("{} expects {} comma separated numbers", name, count);
+        }
+        Some(values)
+    };
+    let print_plan = |plan: Result<Vec<Instruction>, String>, costs: &Costs| match plan {
+        Ok(plan) => {
+            let text: Vec<String> = plan.iter().map(Instruction::to_string).collect();
+            println!("plan: {} (cost {})", text.join(" "), costs.total(&plan));
+        }
+        Err(e) => println!("no plan: {}", e),
+    };
+
+    let costs = numbers("--costs", 3).map_or_else(Costs::default, |c| Costs {
+        shift: c[0] as u32,
+        turn: c[1] as u32,
+        forward: c[2] as u32,
+    });
+    if let Some(t) = numbers("--plan", 3) {
+        print_plan(
+            plan_immediate(&Ferry::new(), (t[0], t[1]), t[2], &costs),
+            &costs,
+        );
+    }
+    if let Some(t) = numbers("--plan-waypoint", 4) {
+        print_plan(
+            plan_relative(&Ferry::new(), (t[0], t[1]), (t[2], t[3]), &costs),
+            &costs,
+        );
+    }
+    if args.iter().any(|a| a == "--trajectory") {
+        print_trajectory("immediate", &ferry.trajectory());
+        print_trajectory("waypoint", &ferry2.trajectory());
+    }
+    if let Some(i) = args.iter().position(|a| a == "--svg") {
+        let path = args.get(i + 1).expect("missing svg file");
+        fs::write(path, to_svg(&ferry.trajectory(), &ferry2.trajectory()))
+            .unwrap_or_else(|e| panic!("can't write {}: {}", path, e));
+    }
 }
 
 #[cfg(test)]
@@ -173,4 +873,131 @@ F11";
         let mut ferry = Ferry::new();
         assert_eq!(Solution::part2(&mut ferry, &parse(input)), 286);
     }
+
+    #[test]
+    fn test_day12_trajectory() {
+        let input = "\
+F10
+N3
+F7
+R90
+F11";
+        let mut ferry = Ferry::new();
+        ferry.process_instructions_immediate(&parse(input));
+        let trajectory = ferry.trajectory();
+        assert_eq!(trajectory.steps.len(), 6);
+        assert_eq!(trajectory.steps[2].position, point(10, 3));
+        assert_eq!(
+            (trajectory.min, trajectory.max),
+            (point(0, -8), point(17, 3))
+        );
+        assert_eq!(trajectory.max_distance, Fixed::from_int(25));
+        assert_eq!(trajectory.total_distance, 31.0);
+
+        let mut ferry = Ferry::new();
+        ferry.process_instructions_relative(&parse(input));
+        let trajectory = ferry.trajectory();
+        assert_eq!(trajectory.steps[4].waypoint, point(4, -10));
+        assert_eq!(
+            (trajectory.min, trajectory.max),
+            (point(0, -72), point(214, 38))
+        );
+        assert_eq!(trajectory.max_distance, Fixed::from_int(286));
+    }
+
+    #[test]
+    fn test_day12_arbitrary_angles() {
+        // R45, L90 and R405 add up to a full turn, leaving the ship facing east again
+        let mut ferry = Ferry::new();
+        ferry.process_instructions_immediate(&parse("R45\nF10\nL90\nF10\nR405"));
+        assert_eq!(ferry.facing, 90);
+        let (x, y) = ferry.position;
+        assert!((x.to_f64() - 2.0 * 50f64.sqrt()).abs() < 1e-5);
+        assert_eq!(y, Fixed::default());
+        assert!((ferry.trajectory().total_distance - 20.0).abs() < 1e-5);
+
+        let mut ferry = Ferry::new();
+        ferry.process_instructions_relative(&parse("L30\nL60\nF1"));
+        assert_eq!(ferry.position, point(-1, 10));
+
+        assert_eq!(Fixed(-1_500_000).to_string(), "-1.5");
+        assert_eq!(Fixed(-1_500_000).round(), -2);
+        assert_eq!(Fixed::from_int(7).to_string(), "7");
+    }
+
+    #[test]
+    fn test_day12_svg() {
+        let instructions = parse("F10\nN3\nF7\nR90\nF11");
+        let mut ferry = Ferry::new();
+        let mut ferry2 = ferry.clone();
+        ferry.process_instructions_immediate(&instructions);
+        ferry2.process_instructions_relative(&instructions);
+
+        let svg = to_svg(&ferry.trajectory(), &ferry2.trajectory());
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<polyline").count(), 2);
+        assert_eq!(svg.matches("<circle").count(), 4);
+        assert!(svg.contains("points=\"0,0 10,0 10,-3 17,-3 17,-3 17,8\""));
+        assert!(svg.contains(">10 units</text>"));
+    }
+
+    #[test]
+    fn test_day12_plan_immediate() {
+        let start = Ferry::new();
+        let costs = Costs::default();
+
+        // already facing east, so moving forward needs no turn
+        let plan = plan_immediate(&start, (17, 0), 90, &costs).unwrap();
+        assert_eq!(plan, vec![Instruction::Forward(17)]);
+        let plan = plan_immediate(&start, (17, -8), 180, &costs).unwrap();
+        assert_eq!(
+            plan,
+            vec![
+                Instruction::Forward(17),
+                Instruction::Right(90),
+                Instruction::Forward(8)
+            ]
+        );
+        let plan = plan_immediate(&start, (0, 0), 45, &costs).unwrap();
+        assert_eq!(plan, vec![Instruction::Left(45)]);
+
+        // expensive turns favour shifts
+        let costs = Costs {
+            shift: 1,
+            turn: 5,
+            forward: 1,
+        };
+        let plan = plan_immediate(&start, (-3, 4), 90, &costs).unwrap();
+        assert_eq!(plan, vec![Instruction::West(3), Instruction::North(4)]);
+    }
+
+    #[test]
+    fn test_day12_plan_relative() {
+        let start = Ferry::new();
+        let costs = Costs::default();
+
+        let plan = plan_relative(&start, (214, -72), (4, -10), &costs).unwrap();
+        assert_eq!(costs.total(&plan), 5);
+        let mut ferry = start.clone();
+        ferry.process_instructions_relative(&plan);
+        assert_eq!(ferry.position, point(214, -72));
+        assert_eq!(ferry.waypoint, point(4, -10));
+
+        // the start waypoint already divides the target, a turn brings it back
+        let plan = plan_relative(&start, (30, 3), (-1, 10), &costs).unwrap();
+        assert_eq!(plan, vec![Instruction::Forward(3), Instruction::Left(90)]);
+
+        // sailing a second leg is cheaper than reshaping the waypoint twice
+        let plan = plan_relative(&start, (21, 2), (10, 1), &costs).unwrap();
+        assert_eq!(costs.total(&plan), 4);
+        let mut ferry = start.clone();
+        ferry.process_instructions_relative(&plan);
+        assert_eq!(ferry.position, point(21, 2));
+        assert_eq!(ferry.waypoint, point(10, 1));
+
+        assert_eq!(
+            plan_relative(&start, (0, 0), (1, -10), &costs),
+            Ok(vec![Instruction::Right(90)])
+        );
+    }
 }
@@ -1,8 +1,9 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::env;
 use std::fs;
 use std::time::Instant;
 
-use ring_algorithm::chinese_remainder_theorem;
-
 type Notes = (usize, Vec<(usize, usize)>);
 
 fn parse(input: &str) -> Notes {
@@ -27,6 +28,157 @@ fn find_next_departures(earliest: usize, buses: &[(usize, usize)]) -> Vec<(usize
         .collect()
 }
 
+// All timestamps t with t ≡ residue (mod period)
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Congruence {
+    residue: i128,
+    period: i128,
+}
+
+// (g, x, y) with a * x + b * y = g = gcd(a, b)
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        (a, 1, 0)
+    } else {
+        let (g, x, y) = extended_gcd(b, a % b);
+        (g, y, x - a / b * y)
+    }
+}
+
+// Combines two congruences whose moduli don't have to be coprime. They only have a
+// common solution if their residues agree modulo the gcd of the moduli.
+fn merge(a: Congruence, b: Congruence) -> Result<Congruence, String> {
+    let (g, x, _) = extended_gcd(a.period, b.period);
+    let diff = b.residue - a.residue;
+    if diff % g != 0 {
+        return Err(format!(
+            "no solution: t = {} (mod {}) and t = {} (mod {}) contradict each other",
+            a.residue, a.period, b.residue, b.period
+        ));
+    }
+
+    let m = b.period / g;
+    let period = a
+        .period
+        .checked_mul(m)
+        .ok_or_else(|| "no solution: period exceeds 128 bits".to_string())?;
+    // x is the inverse of a.period / g modulo m
+    let k = (diff / g).rem_euclid(m) * x.rem_euclid(m) % m;
+    Ok(Congruence {
+        residue: (a.residue + a.period * k).rem_euclid(period),
+        period,
+    })
+}
+
+// Solves t = residue (mod modulus) for all pairs at once
+fn solve_congruences(
+    congruences: impl IntoIterator<Item = (i128, i128)>,
+) -> Result<Congruence, String> {
+    congruences.into_iter().try_fold(
+        Congruence {
+            residue: 0,
+            period: 1,
+        },
+        |acc, (residue, modulus)| {
+            if modulus <= 0 {
+                return Err(format!("invalid modulus {}", modulus));
+            }
+            merge(
+                acc,
+                Congruence {
+                    residue: residue.rem_euclid(modulus),
+                    period: modulus,
+                },
+            )
+        },
+    )
+}
+
+// timestamps at which every bus departs at its offset
+fn aligned_departures(buses: &[(usize, usize)]) -> Result<Congruence, String> {
+    solve_congruences(
+        buses
+            .iter()
+            .map(|&(offset, bus_id)| (-(offset as i128), bus_id as i128)),
+    )
+}
+
+// Queries over the departures of all buses, bus i leaves at every multiple of its ID
+struct Timetable<'a> {
+    buses: &'a [(usize, usize)],
+}
+
+impl<'a> Timetable<'a> {
+    fn new(notes: &'a Notes) -> Self {
+        Timetable { buses: &notes.1 }
+    }
+
+    // the next n departures at or after `time` for every bus
+    fn next_departures(&self, time: usize, n: usize) -> Vec<(usize, Vec<usize>)> {
+        self.buses
+            .iter()
+            .map(|&(_, id)| {
+                let first = time.div_ceil(id) * id;
+                (id, (0..n).map(|k| first + k * id).collect())
+            })
+            .collect()
+    }
+
+    // Timestamps from `time` on where each (bus ID, offset) of the subset departs
+    // `offset` minutes after the timestamp.
+    fn windows(
+        &self,
+        subset: &[(usize, usize)],
+        time: usize,
+    ) -> Result<impl Iterator<Item = usize>, String> {
+        if let Some(&(id, _)) = subset
+            .iter()
+            .find(|(id, _)| !self.buses.iter().any(|b| b.1 == *id))
+        {
+            return Err(format!("bus {} is not in service", id));
+        }
+        let buses: Vec<(usize, usize)> = subset.iter().map(|&(id, offset)| (offset, id)).collect();
+        let c = aligned_departures(&buses)?;
+
+        let time = time as i128;
+        let first = time + (c.residue - time).rem_euclid(c.period);
+        Ok((0..)
+            .map(move |k| first + k * c.period)
+            .map_while(|t| usize::try_from(t).ok()))
+    }
+
+    // all departures in [from, to) by time, buses leaving together share an entry
+    fn day_plan(&self, from: usize, to: usize) -> Vec<(usize, Vec<usize>)> {
+        let mut plan: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for &(_, id) in self.buses {
+            let mut t = from.div_ceil(id) * id;
+            while t < to {
+                plan.entry(t).or_default().push(id);
+                t += id;
+            }
+        }
+        plan.into_iter().collect()
+    }
+
+    // how often each gap in minutes occurs between consecutive departures in [from, to)
+    fn gap_distribution(&self, from: usize, to: usize) -> BTreeMap<usize, usize> {
+        let mut dist = BTreeMap::new();
+        for pair in self.day_plan(from, to).windows(2) {
+            *dist.entry(pair[1].0 - pair[0].0).or_insert(0) += 1;
+        }
+        dist
+    }
+}
+
+fn format_day_plan(plan: &[(usize, Vec<usize>)]) -> String {
+    plan.iter()
+        .map(|(t, ids)| {
+            let ids: Vec<String> = ids.iter().map(usize::to_string).collect();
+            format!("{:>10}  {}\n", t, ids.join(", "))
+        })
+        .collect()
+}
+
 struct Solution;
 
 impl Solution {
@@ -38,29 +190,29 @@ impl Solution {
         id * (departure - earliest)
     }
 
-    fn part2(notes: &Notes) -> usize {
-        let (u, m): (Vec<_>, Vec<_>) = notes
-            .1
-            .iter()
-            .map(|&(i, bus_id)| (-(i as isize), bus_id as isize))
-            .unzip();
-
-        chinese_remainder_theorem(&u, &m)
-            .unwrap()
-            .rem_euclid(m.iter().product::<isize>()) as usize
+    fn part2(notes: &Notes) -> Result<i128, String> {
+        aligned_departures(&notes.1).map(|c| c.residue)
     }
 
-    fn part2_alt(notes: &Notes) -> usize {
-        let mut running_product = 1;
-        let mut ans = 0;
+    // Sieving bus by bus, stepping by the period of the buses aligned so far. Each bus
+    // needs at most bus_id steps, after that its departures repeat without fitting.
+    fn part2_alt(notes: &Notes) -> Result<i128, String> {
+        let mut period: i128 = 1;
+        let mut ans: i128 = 0;
 
         for &(i, int) in notes.1.iter() {
+            let (i, int) = (i as i128, int as i128);
+            let mut steps = 0;
             while (ans + i) % int != 0 {
-                ans += running_product;
+                if steps == int {
+                    return Err(format!("no solution: bus {} never departs in time", int));
+                }
+                ans += period;
+                steps += 1;
             }
-            running_product *= int;
+            period = period / extended_gcd(period, int).0 * int;
         }
-        ans
+        Ok(ans)
     }
 }
 
@@ -77,16 +229,74 @@ fn main() {
     let timer = Instant::now();
     println!(
         "p2: {} (runtime: {:?})",
-        Solution::part2(&notes),
+        Solution::part2(&notes).unwrap_or_else(|e| panic!("{}", e)),
         timer.elapsed()
     );
 
     let timer = Instant::now();
     println!(
         "p2 (alternative): {} (runtime: {:?})",
-        Solution::part2_alt(&notes),
+        Solution::part2_alt(&notes).unwrap_or_else(|e| panic!("{}", e)),
         timer.elapsed()
     );
+
+    if let Ok(c) = aligned_departures(&notes.1) {
+        println!("aligned every {} minutes", c.period);
+    }
+
+    // usage: day13 [--next <n>] [--plan <from>-<to>] [--gaps <from>-<to>]
+    //              [--window <bus>@<offset>,...]
+    let args: Vec<String> = env::args().skip(1).collect();
+    let value = |name: &str| -> Option<&str> {
+        let i = args.iter().position(|a| a == name)?;
+        Some(
+            args.get(i + 1)
+                .unwrap_or_else(|| panic!("missing value for {}", name)),
+        )
+    };
+    let range = |name: &str| -> Option<(usize, usize)> {
+        let bounds: Vec<usize> = value(name)?
+            .split('-')
+            .map(|b| b.parse().expect("invalid time"))
+            .collect();
+        Some((bounds[0], bounds[1]))
+    };
+
+    let timetable = Timetable::new(&notes);
+    if let Some(n) = value("--next") {
+        let n = n.parse().expect("invalid number of departures");
+        for (id, departures) in timetable.next_departures(notes.0, n) {
+            let departures: Vec<String> = departures.iter().map(usize::to_string).collect();
+            println!("bus {}: {}", id, departures.join(" "));
+        }
+    }
+    if let Some((from, to)) = range("--plan") {
+        print!("{}", format_day_plan(&timetable.day_plan(from, to)));
+    }
+    if let Some((from, to)) = range("--gaps") {
+        for (gap, count) in timetable.gap_distribution(from, to) {
+            println!("{} minute gaps: {}", gap, count);
+        }
+    }
+    if let Some(spec) = value("--window") {
+        let subset: Vec<(usize, usize)> = spec
+            .split(',')
+            .map(|b| {
+                let (id, offset) = b.split_once('@').expect("expected <bus>@<offset>");
+                (
+                    id.parse().expect("invalid bus"),
+                    offset.parse().expect("invalid offset"),
+                )
+            })
+            .collect();
+        match timetable.windows(&subset, notes.0) {
+            Ok(windows) => {
+                let windows: Vec<String> = windows.take(5).map(|t| t.to_string()).collect();
+                println!("windows: {}", windows.join(" "));
+            }
+            Err(e) => println!("windows: {}", e),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -100,16 +310,87 @@ mod tests {
 
     #[test]
     fn test_day13_part2() {
-        assert_eq!(Solution::part2(&parse("0\n7,13,x,x,59,x,31,19")), 1068781);
-        assert_eq!(Solution::part2(&parse("0\n17,x,13,19")), 3417);
-        assert_eq!(Solution::part2(&parse("0\n67,7,59,61")), 754018);
-        assert_eq!(Solution::part2(&parse("0\n67,x,7,59,61")), 779210);
-        assert_eq!(Solution::part2(&parse("0\n67,7,x,59,61")), 1261476);
-        assert_eq!(Solution::part2(&parse("0\n1789,37,47,1889")), 1202161486);
+        assert_eq!(
+            Solution::part2(&parse("0\n7,13,x,x,59,x,31,19")),
+            Ok(1068781)
+        );
+        assert_eq!(Solution::part2(&parse("0\n17,x,13,19")), Ok(3417));
+        assert_eq!(Solution::part2(&parse("0\n67,7,59,61")), Ok(754018));
+        assert_eq!(Solution::part2(&parse("0\n67,x,7,59,61")), Ok(779210));
+        assert_eq!(Solution::part2(&parse("0\n67,7,x,59,61")), Ok(1261476));
+        assert_eq!(
+            Solution::part2(&parse("0\n1789,37,47,1889")),
+            Ok(1202161486)
+        );
 
         assert_eq!(
             Solution::part2_alt(&parse("0\n7,13,x,x,59,x,31,19")),
-            1068781
+            Ok(1068781)
         );
     }
+
+    #[test]
+    fn test_day13_non_coprime() {
+        // 6 and 4 share the factor 2: t = 0 (mod 6) and t = -2 (mod 4)
+        let notes = parse("0\n6,x,4");
+        assert_eq!(
+            aligned_departures(&notes.1),
+            Ok(Congruence {
+                residue: 6,
+                period: 12
+            })
+        );
+        assert_eq!(Solution::part2_alt(&notes), Ok(6));
+
+        // t = 0 (mod 6) and t = -1 (mod 4) would need t to be both even and odd
+        let notes = parse("0\n6,4");
+        assert!(Solution::part2(&notes).is_err());
+        assert!(Solution::part2_alt(&notes).is_err());
+
+        let c = aligned_departures(&parse("0\n7,13,x,x,59,x,31,19").1).unwrap();
+        assert_eq!(c.period, 7 * 13 * 59 * 31 * 19);
+        assert!(solve_congruences(vec![(1, 0)]).is_err());
+    }
+
+    #[test]
+    fn test_day13_timetable() {
+        let notes = parse("939\n7,13,x,x,59,x,31,19");
+        let timetable = Timetable::new(&notes);
+
+        let next = timetable.next_departures(939, 2);
+        assert_eq!(next[0], (7, vec![945, 952]));
+        assert_eq!(next[2], (59, vec![944, 1003]));
+        assert_eq!(timetable.next_departures(938, 1)[4], (19, vec![950]));
+
+        let plan = timetable.day_plan(0, 14);
+        assert_eq!(
+            plan,
+            vec![(0, vec![7, 13, 59, 31, 19]), (7, vec![7]), (13, vec![13])]
+        );
+        assert_eq!(
+            format_day_plan(&plan[1..]),
+            "         7  7\n        13  13\n"
+        );
+        assert_eq!(
+            timetable
+                .gap_distribution(0, 15)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![(1, 1), (6, 1), (7, 1)]
+        );
+
+        let windows: Vec<usize> = timetable
+            .windows(&[(7, 0), (13, 1)], 100)
+            .unwrap()
+            .take(2)
+            .collect();
+        assert_eq!(windows, vec![168, 259]);
+        let all: Vec<usize> = timetable
+            .windows(&[(7, 0), (13, 1), (59, 4), (31, 6), (19, 7)], 0)
+            .unwrap()
+            .take(1)
+            .collect();
+        assert_eq!(all, vec![1068781]);
+        assert!(timetable.windows(&[(5, 0)], 0).is_err());
+    }
 }
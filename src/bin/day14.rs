@@ -1,126 +1,436 @@
 use std::collections::HashMap;
+use std::env;
+use std::fmt;
 use std::fs;
+use std::mem;
+use std::str::FromStr;
 use std::time::Instant;
 
+const ADDRESS_BITS: usize = 36;
+const MAX_VALUE: usize = (1 << ADDRESS_BITS) - 1;
+
+// Bitmask split by kind of bit, bit i of each field stands for mask character 35 - i
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Mask {
+    zeros: usize,
+    ones: usize,
+    floating: usize,
+}
+
+impl FromStr for Mask {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != ADDRESS_BITS {
+            return Err(format!("mask '{}' must have {} bits", s, ADDRESS_BITS));
+        }
+        s.chars()
+            .rev()
+            .enumerate()
+            .try_fold(Mask::default(), |mask, (i, c)| match c {
+                '0' => Ok(Mask {
+                    zeros: mask.zeros | 1 << i,
+                    ..mask
+                }),
+                '1' => Ok(Mask {
+                    ones: mask.ones | 1 << i,
+                    ..mask
+                }),
+                'X' => Ok(Mask {
+                    floating: mask.floating | 1 << i,
+                    ..mask
+                }),
+                _ => Err(format!("invalid mask bit '{}'", c)),
+            })
+    }
+}
+
+impl fmt::Display for Mask {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mask: String = (0..ADDRESS_BITS)
+            .rev()
+            .map(|i| match (self.ones >> i & 1, self.floating >> i & 1) {
+                (1, _) => '1',
+                (_, 1) => 'X',
+                _ => '0',
+            })
+            .collect();
+        write!(f, "{}", mask)
+    }
+}
+
 #[derive(Debug)]
 enum Instruction {
-    Mask(String),
+    Mask(Mask),
     Write(usize, usize),
 }
 
-fn parse(input: &str) -> Vec<Instruction> {
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instruction::Mask(mask) => write!(f, "mask = {}", mask),
+            Instruction::Write(address, value) => write!(f, "mem[{}] = {}", address, value),
+        }
+    }
+}
+
+fn parse(input: &str) -> Result<Vec<Instruction>, String> {
     input
         .lines()
         .map(|line| {
             let inst: Vec<&str> = line.split('=').map(str::trim).collect();
-            match inst[0] {
-                "mask" => Instruction::Mask(inst[1].to_owned()),
-                token if token.starts_with("mem") => {
-                    let address: Vec<&str> = token.split(|p| p == '[' || p == ']').collect();
-                    let address = address[1].trim().parse::<usize>().unwrap();
-                    Instruction::Write(address, inst[1].parse::<usize>().unwrap())
+            match inst[..] {
+                ["mask", mask] => Ok(Instruction::Mask(mask.parse()?)),
+                [token, value] if token.starts_with("mem") => {
+                    let address: Vec<&str> = token.split(['[', ']']).collect();
+                    let number = |s: &str| match s.trim().parse::<usize>() {
+                        Ok(n) if n <= MAX_VALUE => Ok(n),
+                        Ok(n) => Err(format!("{} doesn't fit into {} bits", n, ADDRESS_BITS)),
+                        Err(e) => Err(format!("invalid number '{}': {}", s, e)),
+                    };
+                    let address = number(address.get(1).ok_or("missing address")?)?;
+                    Ok(Instruction::Write(address, number(value)?))
                 }
-                _ => panic!("unknown instruction: {}", line),
+                _ => Err(format!("unknown instruction: {}", line)),
             }
         })
         .collect()
 }
 
-// Returns two bitmasks: (AND-mask, OR-mask)
-// AND-mask to set 0's (clear 1's)
-// OR-mask to set 1's
-fn create_masks_v1(raw_mask: &str) -> (usize, usize) {
-    raw_mask
-        .chars()
-        .rev()
-        .enumerate()
-        .filter(|(_, c)| *c == '0' || *c == '1')
-        .fold((usize::MAX, 0_usize), |(and, or), (i, c)| match c {
-            '0' => (and & !(1 << i), or),
-            '1' => (and, or | (1 << i)),
-            _ => (and, or),
-        })
+// Set of addresses: every bit set in `floating` takes both values, all other bits are
+// those of `address`. Floating bits are always cleared in `address`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct AddressPattern {
+    address: usize,
+    floating: usize,
 }
 
-fn calculate_dest_addresses(raw_mask: &str, raw_address: usize) -> Vec<usize> {
-    let mut res = vec![];
-    let len = raw_mask.chars().filter(|&c| c == 'X').count();
+impl AddressPattern {
+    fn new(address: usize, floating: usize) -> Self {
+        AddressPattern {
+            address: address & !floating,
+            floating,
+        }
+    }
 
-    for mut i in 0..(1 << len) {
-        let mut tmp_mask = raw_mask
-            .chars()
-            .zip(format!("{:036b}", raw_address).chars())
-            .map(|(a, b)| match (a, b) {
-                ('X', _) => 'X',
-                ('1', _) => '1',
-                ('0', val) => val,
-                _ => panic!("given bitmask not supported!"),
-            })
-            .collect::<String>();
+    fn len(&self) -> u128 {
+        1 << self.floating.count_ones()
+    }
+
+    fn overlaps(&self, other: &AddressPattern) -> bool {
+        (self.address ^ other.address) & !(self.floating | other.floating) == 0
+    }
 
-        for _ in 0..len {
-            tmp_mask = tmp_mask.replacen('X', &format!("{}", i & 1), 1);
-            i >>= 1;
+    // Splits off the addresses of self that are not in other, as disjoint patterns.
+    // Each bit floating here but fixed in other yields one piece with that bit set
+    // against other, at most one piece per address bit.
+    fn subtract(&self, other: &AddressPattern) -> Vec<AddressPattern> {
+        if !self.overlaps(other) {
+            return vec![*self];
         }
-        res.push(usize::from_str_radix(&tmp_mask, 2).unwrap());
+
+        let mut pieces = vec![];
+        let mut rest = *self;
+        for bit in (0..ADDRESS_BITS).map(|i| 1 << i) {
+            if rest.floating & bit != 0 && other.floating & bit == 0 {
+                rest.floating &= !bit;
+                pieces.push(AddressPattern {
+                    address: rest.address | (other.address & bit ^ bit),
+                    floating: rest.floating,
+                });
+                rest.address |= other.address & bit;
+            }
+        }
+        pieces
     }
-    res
 }
 
-fn write_to_mem(
-    version: usize,
-    mem: &mut HashMap<usize, usize>,
-    address: usize,
-    mask: &str,
-    new_value: usize,
-) {
-    match version {
-        1 => {
-            let (and, or) = create_masks_v1(mask);
-            mem.insert(address, (new_value & and) | or);
+// Addresses shared by both patterns
+fn intersection(a: &AddressPattern, b: &AddressPattern) -> Option<AddressPattern> {
+    if !a.overlaps(b) {
+        return None;
+    }
+    Some(AddressPattern {
+        address: a.address | b.address,
+        floating: a.floating & b.floating,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Radix {
+    Hex,
+    Binary,
+}
+
+impl FromStr for Radix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hex" => Ok(Radix::Hex),
+            "bin" => Ok(Radix::Binary),
+            _ => Err(format!("unknown radix '{}', expected hex or bin", s)),
         }
-        2 => {
-            for dest_address in calculate_dest_addresses(mask, address) {
-                mem.insert(dest_address, new_value);
-            }
+    }
+}
+
+impl Radix {
+    fn value(self, value: usize) -> String {
+        match self {
+            Radix::Hex => format!("0x{:09x}", value),
+            Radix::Binary => format!("{:036b}", value),
+        }
+    }
+
+    // floating bits are X's in binary, and listed separately in hex
+    fn pattern(self, p: &AddressPattern) -> String {
+        match self {
+            Radix::Hex if p.floating == 0 => self.value(p.address),
+            Radix::Hex => format!("0x{:09x} X 0x{:09x}", p.address, p.floating),
+            Radix::Binary => (0..ADDRESS_BITS)
+                .rev()
+                .map(|i| match (p.floating >> i & 1, p.address >> i & 1) {
+                    (1, _) => 'X',
+                    (_, 1) => '1',
+                    _ => '0',
+                })
+                .collect(),
         }
-        _ => (),
     }
 }
 
-fn process(version: usize, memory: &mut HashMap<usize, usize>, instructions: &[Instruction]) {
-    let mut mask = String::from("xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx");
-    for inst in instructions.iter() {
-        match inst {
-            Instruction::Mask(new_mask) => {
-                mask = new_mask.clone();
+// Memory cell, a value written to all addresses of a pattern by the given instruction
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Cell {
+    pattern: AddressPattern,
+    value: usize,
+    written_by: usize,
+}
+
+// Memory written through address patterns, the patterns are kept pairwise disjoint so
+// the memory can be summed up without enumerating single addresses.
+#[derive(Debug, Default)]
+struct PatternMemory {
+    // cells of a single address by address, so that writes without floating bits stay
+    // a map insert as long as no pattern overlaps them
+    singles: HashMap<usize, Cell>,
+    // cells with floating bits
+    patterns: Vec<Cell>,
+    // index of the instruction being executed
+    instruction: usize,
+}
+
+impl PatternMemory {
+    fn insert(&mut self, cell: Cell) {
+        if cell.pattern.floating == 0 {
+            self.singles.insert(cell.pattern.address, cell);
+        } else {
+            self.patterns.push(cell);
+        }
+    }
+
+    fn write(&mut self, pattern: AddressPattern, value: usize) {
+        // a single address replaces its own cell on insert
+        if pattern.floating != 0 {
+            self.singles.retain(|_, c| !c.pattern.overlaps(&pattern));
+        }
+        for cell in mem::take(&mut self.patterns) {
+            for piece in cell.pattern.subtract(&pattern) {
+                self.insert(Cell {
+                    pattern: piece,
+                    ..cell
+                });
             }
-            Instruction::Write(address, new_value) => {
-                write_to_mem(version, memory, *address, &mask, *new_value)
+        }
+        self.insert(Cell {
+            pattern,
+            value,
+            written_by: self.instruction,
+        });
+    }
+
+    fn cells(&self) -> impl Iterator<Item = &Cell> {
+        self.singles.values().chain(self.patterns.iter())
+    }
+
+    fn sum(&self) -> u128 {
+        self.cells()
+            .map(|c| c.pattern.len() * c.value as u128)
+            .sum()
+    }
+
+    fn cell(&self, address: usize) -> Option<&Cell> {
+        self.singles.get(&address).or_else(|| {
+            self.patterns
+                .iter()
+                .find(|c| c.pattern.overlaps(&AddressPattern::new(address, 0)))
+        })
+    }
+
+    // cells ordered by address, floating bits count as zeros
+    fn sorted(&self) -> Vec<Cell> {
+        let mut cells: Vec<Cell> = self.cells().copied().collect();
+        cells.sort_unstable_by_key(|c| c.pattern);
+        cells
+    }
+
+    fn dump(&self, radix: Radix) -> String {
+        self.sorted()
+            .iter()
+            .map(|c| format!("{} = {}\n", radix.pattern(&c.pattern), radix.value(c.value)))
+            .collect()
+    }
+
+    // which instruction last wrote each address
+    fn write_log(&self, instructions: &[Instruction], radix: Radix) -> String {
+        self.sorted()
+            .iter()
+            .map(|c| {
+                format!(
+                    "{} <- #{} {}\n",
+                    radix.pattern(&c.pattern),
+                    c.written_by,
+                    instructions[c.written_by]
+                )
+            })
+            .collect()
+    }
+}
+
+// Addresses holding different values in two memory images, None where one of them was
+// never written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Difference {
+    pattern: AddressPattern,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+fn diff(left: &PatternMemory, right: &PatternMemory) -> Vec<Difference> {
+    // parts of a cell not covered by any cell of the other memory
+    fn uncovered(cell: &Cell, other: &PatternMemory) -> Vec<AddressPattern> {
+        other.cells().fold(vec![cell.pattern], |rest, o| {
+            rest.iter().flat_map(|p| p.subtract(&o.pattern)).collect()
+        })
+    }
+
+    let mut diffs = vec![];
+    for l in left.cells() {
+        for r in right.cells() {
+            match intersection(&l.pattern, &r.pattern) {
+                Some(pattern) if l.value != r.value => diffs.push(Difference {
+                    pattern,
+                    left: Some(l.value),
+                    right: Some(r.value),
+                }),
+                _ => (),
             }
         }
+        diffs.extend(uncovered(l, right).into_iter().map(|pattern| Difference {
+            pattern,
+            left: Some(l.value),
+            right: None,
+        }));
+    }
+    for r in right.cells() {
+        diffs.extend(uncovered(r, left).into_iter().map(|pattern| Difference {
+            pattern,
+            left: None,
+            right: Some(r.value),
+        }));
+    }
+    diffs.sort_unstable_by_key(|d| d.pattern);
+    diffs
+}
+
+fn format_diff(diffs: &[Difference], radix: Radix) -> String {
+    let value = |v: Option<usize>| v.map_or("-".to_string(), |v| radix.value(v));
+    diffs
+        .iter()
+        .map(|d| {
+            format!(
+                "{}: {} | {}\n",
+                radix.pattern(&d.pattern),
+                value(d.left),
+                value(d.right)
+            )
+        })
+        .collect()
+}
+
+// Translates a write through the current mask into memory writes
+trait DecoderChip {
+    fn version(&self) -> usize;
+
+    fn write(&self, memory: &mut PatternMemory, mask: &Mask, address: usize, value: usize);
+}
+
+// version 1 masks the value, zeros clear bits and ones set them
+struct ValueDecoder;
+
+impl DecoderChip for ValueDecoder {
+    fn version(&self) -> usize {
+        1
+    }
+
+    fn write(&self, memory: &mut PatternMemory, mask: &Mask, address: usize, value: usize) {
+        let value = value & !mask.zeros | mask.ones;
+        memory.write(AddressPattern::new(address, 0), value);
     }
 }
 
+// version 2 masks the address, ones set bits and X's write to both values of a bit
+struct AddressDecoder;
+
+impl DecoderChip for AddressDecoder {
+    fn version(&self) -> usize {
+        2
+    }
+
+    fn write(&self, memory: &mut PatternMemory, mask: &Mask, address: usize, value: usize) {
+        memory.write(
+            AddressPattern::new(address | mask.ones, mask.floating),
+            value,
+        );
+    }
+}
+
+fn decoder_chip(version: usize) -> Result<Box<dyn DecoderChip>, String> {
+    match version {
+        1 => Ok(Box::new(ValueDecoder)),
+        2 => Ok(Box::new(AddressDecoder)),
+        _ => Err(format!("unknown decoder chip version {}", version)),
+    }
+}
+
+fn process(chip: &dyn DecoderChip, instructions: &[Instruction]) -> PatternMemory {
+    let mut memory = PatternMemory::default();
+    let mut mask = Mask::default();
+    for (i, inst) in instructions.iter().enumerate() {
+        memory.instruction = i;
+        match inst {
+            Instruction::Mask(new_mask) => mask = *new_mask,
+            Instruction::Write(address, value) => chip.write(&mut memory, &mask, *address, *value),
+        }
+    }
+    memory
+}
+
 struct Solution;
 
 impl Solution {
-    fn part1(instructions: &[Instruction]) -> usize {
-        let mut memory = HashMap::new();
-        process(1, &mut memory, instructions);
-        memory.values().sum()
+    fn part1(instructions: &[Instruction]) -> u128 {
+        process(&ValueDecoder, instructions).sum()
     }
 
-    fn part2(instructions: &[Instruction]) -> usize {
-        let mut memory = HashMap::new();
-        process(2, &mut memory, instructions);
-        memory.values().sum()
+    fn part2(instructions: &[Instruction]) -> u128 {
+        process(&AddressDecoder, instructions).sum()
     }
 }
 
 fn main() {
     let input = fs::read_to_string("./input/day14.txt").expect("File not found!");
-    let instructions = parse(&input);
+    let instructions = parse(input.trim()).unwrap_or_else(|e| panic!("{}", e));
 
     let timer = Instant::now();
     println!(
@@ -134,6 +444,48 @@ fn main() {
         Solution::part2(&instructions),
         timer.elapsed()
     );
+
+    // usage: day14 [--version <n>] [--dump hex|bin] [--log hex|bin] [--diff hex|bin]
+    //              [--get <address>]
+    let args: Vec<String> = env::args().skip(1).collect();
+    let value = |name: &str| -> Option<&str> {
+        let i = args.iter().position(|a| a == name)?;
+        Some(
+            args.get(i + 1)
+                .unwrap_or_else(|| panic!("missing value for {}", name)),
+        )
+    };
+    let radix = |name: &str| -> Option<Radix> {
+        value(name).map(|r| r.parse().unwrap_or_else(|e| panic!("{}", e)))
+    };
+
+    let version = value("--version").map_or(2, |v| v.parse().expect("invalid version"));
+    let chip = decoder_chip(version).unwrap_or_else(|e| panic!("{}", e));
+    let memory = process(chip.as_ref(), &instructions);
+    if value("--version").is_some() {
+        println!("v{}: {}", chip.version(), memory.sum());
+    }
+    if let Some(radix) = radix("--dump") {
+        print!("{}", memory.dump(radix));
+    }
+    if let Some(radix) = radix("--log") {
+        print!("{}", memory.write_log(&instructions, radix));
+    }
+    if let Some(address) = value("--get") {
+        let address = address.parse().expect("invalid address");
+        match memory.cell(address) {
+            Some(c) => println!(
+                "mem[{}] = {} (written by #{} {})",
+                address, c.value, c.written_by, instructions[c.written_by]
+            ),
+            None => println!("mem[{}] was never written", address),
+        }
+    }
+    if let Some(radix) = radix("--diff") {
+        let v1 = process(&ValueDecoder, &instructions);
+        let v2 = process(&AddressDecoder, &instructions);
+        print!("{}", format_diff(&diff(&v1, &v2), radix));
+    }
 }
 
 #[cfg(test)]
@@ -147,7 +499,7 @@ mask = XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X
 mem[8] = 11
 mem[7] = 101
 mem[8] = 0";
-        assert_eq!(Solution::part1(&parse(input)), 165);
+        assert_eq!(Solution::part1(&parse(input).unwrap()), 165);
     }
 
     #[test]
@@ -157,6 +509,107 @@ mask = 000000000000000000000000000000X1001X
 mem[42] = 100
 mask = 00000000000000000000000000000000X0XX
 mem[26] = 1";
-        assert_eq!(Solution::part2(&parse(input)), 208);
+        assert_eq!(Solution::part2(&parse(input).unwrap()), 208);
+    }
+
+    #[test]
+    fn test_day14_address_patterns() {
+        let a = AddressPattern::new(0b011010, 0b100001);
+        assert_eq!(a.len(), 4);
+
+        let b = AddressPattern::new(0b011010, 0b001011);
+        let pieces = a.subtract(&b);
+        assert_eq!(pieces.iter().map(AddressPattern::len).sum::<u128>(), 2);
+        assert!(pieces.iter().all(|p| !p.overlaps(&b)));
+        assert_eq!(a.subtract(&AddressPattern::new(0, 0)), vec![a]);
+
+        // single addresses and patterns replace each other
+        let mut memory = PatternMemory::default();
+        memory.write(AddressPattern::new(5, 0), 1);
+        memory.write(AddressPattern::new(4, 0b11), 2);
+        memory.write(AddressPattern::new(6, 0), 3);
+        assert_eq!(memory.sum(), 2 * 3 + 3);
+        assert_eq!(memory.cell(5).map(|c| c.value), Some(2));
+        assert_eq!(memory.cell(6).map(|c| c.value), Some(3));
+        assert_eq!(memory.singles.len(), 2);
+
+        // 34 floating bits would mean billions of single addresses
+        let input = "\
+mask = 0XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX1
+mem[0] = 3
+mask = 00XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX0X
+mem[1] = 5";
+        // the second write covers 2^32 addresses of the first one
+        let total = 3 * ((1u128 << 34) - (1 << 32)) + 5 * (1 << 33);
+        assert_eq!(Solution::part2(&parse(input).unwrap()), total);
+    }
+
+    #[test]
+    fn test_day14_decoder_chips() {
+        let mask: Mask = "XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X".parse().unwrap();
+        assert_eq!((mask.zeros, mask.ones), (0b10, 0b1000000));
+        assert_eq!(mask.floating.count_ones(), 34);
+        assert!("X1001X".parse::<Mask>().is_err());
+        assert!("XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0Y"
+            .parse::<Mask>()
+            .is_err());
+
+        assert!(parse("mem[8] = 68719476735").is_ok());
+        assert!(parse("mem[8] = 68719476736").is_err());
+        assert!(parse("mem[68719476736] = 1").is_err());
+        assert!(parse("memory").is_err());
+
+        assert!(decoder_chip(3).is_err());
+        let chip = decoder_chip(2).unwrap();
+        let instructions = parse("mem[3] = 7").unwrap();
+        assert_eq!(chip.version(), 2);
+        assert_eq!(process(chip.as_ref(), &instructions).sum(), 7);
+    }
+
+    #[test]
+    fn test_day14_inspection() {
+        let input = "\
+mask = 000000000000000000000000000000X1001X
+mem[42] = 100
+mask = 00000000000000000000000000000000X0XX
+mem[26] = 1";
+        let instructions = parse(input).unwrap();
+        assert_eq!(instructions[0].to_string(), input.lines().next().unwrap());
+
+        let v2 = process(&AddressDecoder, &instructions);
+        assert_eq!(v2.cell(59).map(|c| (c.value, c.written_by)), Some((100, 1)));
+        assert_eq!(v2.cell(27).map(|c| (c.value, c.written_by)), Some((1, 3)));
+        assert_eq!(v2.cell(60), None);
+
+        let dump = v2.dump(Radix::Binary);
+        assert_eq!(dump.lines().count(), 2);
+        assert!(dump.starts_with("00000000000000000000000000000001X0XX = "));
+        assert_eq!(
+            v2.write_log(&instructions, Radix::Hex).lines().last(),
+            Some("0x00000003a X 0x000000001 <- #1 mem[42] = 100")
+        );
+
+        let v1 = process(&ValueDecoder, &instructions);
+        assert_eq!(
+            v1.dump(Radix::Hex),
+            "0x00000001a = 0x000000001\n0x00000002a = 0x000000032\n"
+        );
+        let diffs = diff(&v1, &v2);
+        // address 26 holds 1 in both images, the rest of v2 was never written in v1
+        assert!(diffs
+            .iter()
+            .all(|d| d.pattern.address != 26 || d.pattern.floating != 0));
+        let only_v2: u128 = diffs
+            .iter()
+            .filter(|d| d.left.is_none())
+            .map(|d| d.pattern.len())
+            .sum();
+        assert_eq!(only_v2, 9);
+        assert!(diffs.contains(&Difference {
+            pattern: AddressPattern::new(42, 0),
+            left: Some(50),
+            right: None
+        }));
+        assert!(diff(&v2, &v2).is_empty());
     }
 }
@@ -1,38 +1,241 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
 use std::time::Instant;
 
+use aoc2020::checkpoint::Checkpoint;
+
 fn parse(input: &str) -> Vec<usize> {
-    input.split(',').filter_map(|n| n.parse().ok()).collect()
+    input
+        .trim()
+        .split(',')
+        .filter_map(|n| n.parse().ok())
+        .collect()
 }
 
-struct Solution;
+// Numbers below this limit are tracked in the last-seen array by default
+const DENSE_LIMIT: usize = 1 << 22;
 
-impl Solution {
-    fn solve(starting_numbers: &[usize], target: usize) -> usize {
-        let mut spoken: Vec<(usize, usize)> = vec![(0, 0); target + 1];
+// Generates the memory game sequence (the Van Eck sequence after the starting
+// numbers). The turn a number was last spoken at is kept in a u32 array for numbers
+// below `dense_limit`; the rare higher numbers go into a hash map instead.
+struct VanEck {
+    starting: Vec<usize>,
+    last_seen: Vec<u32>,
+    sparse: HashMap<usize, u32>,
+    dense_limit: usize,
+    // turns played so far
+    turn: u32,
+    // number spoken last and to be spoken next, unless it's still a starting number's turn
+    last: usize,
+    next: usize,
+}
+
+impl VanEck {
+    fn new(starting: &[usize]) -> Self {
+        Self::with_dense_limit(starting, DENSE_LIMIT)
+    }
+
+    fn with_dense_limit(starting: &[usize], dense_limit: usize) -> Self {
+        VanEck {
+            starting: starting.to_vec(),
+            last_seen: vec![],
+            sparse: HashMap::new(),
+            dense_limit,
+            turn: 0,
+            last: 0,
+            next: 0,
+        }
+    }
 
-        let len_starting = starting_numbers.len();
-        for (i, &num) in starting_numbers.iter().enumerate() {
-            spoken[num] = (i + 1, 0);
+    // Replaces the turn `number` was last spoken at, 0 for never
+    fn replace_last_seen(&mut self, number: usize, turn: u32) -> u32 {
+        if number >= self.dense_limit {
+            return self.sparse.insert(number, turn).unwrap_or(0);
+        }
+        if number >= self.last_seen.len() {
+            let len = (number + 1).max(2 * self.last_seen.len());
+            self.last_seen.resize(len.min(self.dense_limit), 0);
         }
+        std::mem::replace(&mut self.last_seen[number], turn)
+    }
+}
+
+impl Iterator for VanEck {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let turn = self.turn.checked_add(1)?;
+        let number = match self.starting.get(turn as usize - 1) {
+            Some(&n) => n,
+            None => self.next,
+        };
+
+        self.next = match self.replace_last_seen(number, turn) {
+            0 => 0,
+            last => (turn - last) as usize,
+        };
+        self.turn = turn;
+        self.last = number;
+        Some(number)
+    }
+}
+
+const CHECKPOINT_TAG: &str = "day15";
+
+impl VanEck {
+    fn to_checkpoint(&self) -> Checkpoint {
+        let mut sparse: Vec<u64> = Vec::with_capacity(2 * self.sparse.len());
+        for (&number, &turn) in &self.sparse {
+            sparse.extend(&[number as u64, u64::from(turn)]);
+        }
+        Checkpoint {
+            tag: CHECKPOINT_TAG.to_string(),
+            sections: vec![
+                self.starting.iter().map(|&n| n as u64).collect(),
+                vec![
+                    self.dense_limit as u64,
+                    u64::from(self.turn),
+                    self.last as u64,
+                    self.next as u64,
+                ],
+                self.last_seen.iter().map(|&t| u64::from(t)).collect(),
+                sparse,
+            ],
+        }
+    }
+
+    fn from_checkpoint(checkpoint: &Checkpoint) -> Result<Self, String> {
+        let malformed = || "malformed day 15 checkpoint".to_string();
+        let turn = |t: u64| u32::try_from(t).map_err(|_| malformed());
+        let number = |n: u64| usize::try_from(n).map_err(|_| malformed());
+
+        match &checkpoint.sections[..] {
+            [starting, state, last_seen, sparse] if state.len() == 4 => Ok(VanEck {
+                starting: starting
+                    .iter()
+                    .map(|&n| number(n))
+                    .collect::<Result<_, _>>()?,
+                last_seen: last_seen
+                    .iter()
+                    .map(|&t| turn(t))
+                    .collect::<Result<_, _>>()?,
+                sparse: sparse
+                    .chunks_exact(2)
+                    .map(|kv| Ok((number(kv[0])?, turn(kv[1])?)))
+                    .collect::<Result<_, String>>()?,
+                dense_limit: number(state[0])?,
+                turn: turn(state[1])?,
+                last: number(state[2])?,
+                next: number(state[3])?,
+            }),
+            _ => Err(malformed()),
+        }
+    }
+}
+
+// Plays until the target turn and returns the number spoken then. The state is saved
+// every `every` turns (never for 0) and at the end, and a run picks up from an
+// existing checkpoint. Turns are counted in 32 bits, later targets are rejected.
+fn run_with_checkpoints(
+    starting: &[usize],
+    target: u64,
+    every: u64,
+    path: &Path,
+) -> io::Result<usize> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let target = u32::try_from(target).map_err(|_| {
+        invalid(format!(
+            "turn {} is beyond the last trackable turn {}",
+            target,
+            u32::MAX
+        ))
+    })?;
+
+    let mut game = if path.exists() {
+        let game =
+            VanEck::from_checkpoint(&Checkpoint::load(path, CHECKPOINT_TAG)?).map_err(invalid)?;
+        if game.starting != starting {
+            return Err(invalid("checkpoint has other starting numbers".to_string()));
+        }
+        if game.turn > target {
+            return Err(invalid(format!(
+                "checkpoint is already at turn {}",
+                game.turn
+            )));
+        }
+        game
+    } else {
+        VanEck::new(starting)
+    };
+
+    while game.turn < target {
+        game.next();
+        if every > 0 && u64::from(game.turn) % every == 0 {
+            game.to_checkpoint().save(path)?;
+        }
+    }
+    game.to_checkpoint().save(path)?;
+    Ok(game.last)
+}
+
+// number spoken at the given turn, counting from 1
+fn spoken_at(starting: &[usize], turn: usize) -> Option<usize> {
+    VanEck::new(starting).nth(turn.checked_sub(1)?)
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Statistics {
+    // how often each number was spoken
+    counts: HashMap<usize, usize>,
+    // number with the most turns between two times it was spoken, and those turns
+    largest_gap: Option<(usize, usize)>,
+}
 
-        let mut last_spoken = starting_numbers[len_starting - 1];
-        for turn in len_starting + 1..=target {
-            match spoken[last_spoken] {
-                (last, sec_last) if sec_last != 0 => {
-                    last_spoken = last - sec_last;
-                }
-                _ => last_spoken = 0,
-            };
-            let (last, _) = spoken[last_spoken];
-            spoken[last_spoken] = (turn, if last != 0 { last } else { 0 });
+impl Statistics {
+    fn most_spoken(&self, n: usize) -> Vec<(usize, usize)> {
+        let mut counts: Vec<(usize, usize)> = self.counts.iter().map(|(&k, &v)| (k, v)).collect();
+        counts.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts.truncate(n);
+        counts
+    }
+}
+
+// Statistics over the first `turns` turns. A number spoken after a repeated number
+// is the gap between the last two times that number was spoken.
+fn statistics(starting: &[usize], turns: usize) -> Statistics {
+    let mut stats = Statistics::default();
+    let mut previous = None;
+    for (i, number) in VanEck::new(starting).take(turns).enumerate() {
+        *stats.counts.entry(number).or_insert(0) += 1;
+        if let Some(previous) = previous.filter(|_| i >= starting.len()) {
+            if number > 0 && stats.largest_gap.is_none_or(|(_, gap)| number > gap) {
+                stats.largest_gap = Some((previous, number));
+            }
         }
-        last_spoken
+        previous = Some(number);
+    }
+    stats
+}
+
+struct Solution;
+
+impl Solution {
+    fn solve(starting_numbers: &[usize], target: usize) -> usize {
+        // numbers spoken can't exceed the number of turns
+        VanEck::with_dense_limit(starting_numbers, target)
+            .nth(target - 1)
+            .unwrap()
     }
 }
 
 fn main() {
-    let input = "5,2,8,16,18,0,1";
-    let starting_numbers = parse(input);
+    let input = fs::read_to_string("./input/day15.txt").expect("File not found!");
+    let starting_numbers = parse(&input);
 
     let timer = Instant::now();
     println!(
@@ -46,6 +249,47 @@ fn main() {
         Solution::solve(&starting_numbers, 30_000_000),
         timer.elapsed()
     );
+
+    // usage: day15 [--turn <n>] [--stats <turns>]
+    //              [--checkpoint <file> --until <turn> [--every <turns>]]
+    let args: Vec<String> = env::args().skip(1).collect();
+    fn option<T: FromStr>(args: &[String], name: &str) -> Option<T> {
+        let i = args.iter().position(|a| a == name)?;
+        Some(
+            args.get(i + 1)
+                .and_then(|n| n.parse().ok())
+                .unwrap_or_else(|| panic!("invalid value for {}", name)),
+        )
+    }
+
+    if let Some(turn) = option::<usize>(&args, "--turn") {
+        match spoken_at(&starting_numbers, turn) {
+            Some(n) => println!("turn {}: {}", turn, n),
+            None => println!("turn {}: out of range", turn),
+        }
+    }
+    if let Some(i) = args.iter().position(|a| a == "--checkpoint") {
+        let path = Path::new(args.get(i + 1).expect("missing checkpoint file"));
+        let target: u64 = option(&args, "--until").expect("missing --until");
+        let every = option(&args, "--every").unwrap_or(10_000_000);
+        match run_with_checkpoints(&starting_numbers, target, every, path) {
+            Ok(n) => println!("turn {}: {}", target, n),
+            Err(e) => println!("checkpoint: {}", e),
+        }
+    }
+    if let Some(turns) = option::<usize>(&args, "--stats") {
+        let stats = statistics(&starting_numbers, turns);
+        println!("distinct numbers: {}", stats.counts.len());
+        for (number, count) in stats.most_spoken(5) {
+            println!("{} spoken {} times", number, count);
+        }
+        if let Some((number, gap)) = stats.largest_gap {
+            println!(
+                "largest gap: {} turns before {} was spoken again",
+                gap, number
+            );
+        }
+    }
 }
 
 #[cfg(test)]
@@ -57,4 +301,49 @@ mod tests {
         let input = "0,3,6";
         assert_eq!(Solution::solve(&parse(input), 2020), 436);
     }
+
+    #[test]
+    fn test_day15_generator() {
+        let starting = parse("0,3,6\n");
+        let sequence: Vec<usize> = VanEck::new(&starting).take(10).collect();
+        assert_eq!(sequence, vec![0, 3, 6, 0, 3, 3, 1, 0, 4, 0]);
+        assert_eq!(spoken_at(&starting, 9), Some(4));
+        assert_eq!(spoken_at(&starting, 0), None);
+
+        // a tiny array pushes almost every number into the hash map
+        let sparse: Vec<usize> = VanEck::with_dense_limit(&starting, 2).take(2020).collect();
+        assert_eq!(sparse[2019], 436);
+        assert!(VanEck::new(&starting).take(2020).eq(sparse.into_iter()));
+
+        let stats = statistics(&starting, 10);
+        assert_eq!(stats.counts[&0], 4);
+        assert_eq!(stats.most_spoken(2), vec![(0, 4), (3, 3)]);
+        assert_eq!(stats.largest_gap, Some((0, 4)));
+    }
+
+    #[test]
+    fn test_day15_checkpoints() {
+        let path = env::temp_dir().join(format!("aoc2020-day15-{}.ckpt", std::process::id()));
+        let starting = parse("0,3,6");
+
+        // stopping and resuming gives the same numbers as a single run
+        assert_eq!(run_with_checkpoints(&starting, 2, 1, &path).unwrap(), 3);
+        assert_eq!(
+            run_with_checkpoints(&starting, 1000, 300, &path).unwrap(),
+            spoken_at(&starting, 1000).unwrap()
+        );
+        assert_eq!(
+            run_with_checkpoints(&starting, 2020, 300, &path).unwrap(),
+            436
+        );
+        assert_eq!(
+            run_with_checkpoints(&starting, 2020, 300, &path).unwrap(),
+            436
+        );
+
+        assert!(run_with_checkpoints(&starting, 10, 300, &path).is_err());
+        assert!(run_with_checkpoints(&starting, 5_000_000_000, 300, &path).is_err());
+        assert!(run_with_checkpoints(&parse("1,3,2"), 3000, 300, &path).is_err());
+        fs::remove_file(path).unwrap();
+    }
 }
@@ -17,10 +17,7 @@ fn parse_ticket_field(line: &str) -> (String, Vec<RangeInclusive<usize>>) {
     assert_eq!(token.len(), 2);
 
     let name = token[0];
-    let range_elements: Vec<usize> = token[1]
-        .split(|p| p == ' ' || p == '-')
-        .flat_map(str::parse)
-        .collect();
+    let range_elements: Vec<usize> = token[1].split([' ', '-']).flat_map(str::parse).collect();
     let valid_ranges: Vec<RangeInclusive<usize>> = range_elements
         .chunks(2)
         .map(|chunk| chunk[0]..=chunk[1])
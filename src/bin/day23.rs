@@ -1,6 +1,12 @@
 use std::char;
+use std::convert::TryFrom;
+use std::env;
+use std::io;
+use std::path::Path;
 use std::time::Instant;
 
+use aoc2020::checkpoint::Checkpoint;
+
 fn parse(input: &str) -> Vec<usize> {
     input
         .trim()
@@ -44,19 +50,154 @@ fn single_move(cups_list: &mut [usize], current_cup: usize) -> usize {
     cups_list[current_cup]
 }
 
-struct Solution;
+// Builds the poor man's linked list of num_cups cups: cups_list[c] is the cup after c.
+// The given cups come first, followed by all higher labels in order.
+fn build_cups_list(cups: &[usize], num_cups: usize) -> Result<Vec<usize>, String> {
+    if cups.is_empty() || num_cups < cups.len() {
+        return Err(format!(
+            "can't place {} starting cups among {} cups",
+            cups.len(),
+            num_cups
+        ));
+    }
 
-impl Solution {
-    fn part1(cups: Vec<usize>) -> String {
-        let num_cups = cups.len();
+    let mut cups_list: Vec<usize> = (1..=(num_cups + 1)).collect();
+    for w in cups.windows(2) {
+        cups_list[w[0]] = w[1];
+    }
+    if num_cups > cups.len() {
+        cups_list[cups[cups.len() - 1]] = cups.len() + 1;
+        cups_list[num_cups] = cups[0];
+    } else {
+        cups_list[cups[cups.len() - 1]] = cups[0];
+    }
+    cups_list[0] = 0;
+    Ok(cups_list)
+}
+
+const CHECKPOINT_TAG: &str = "day23";
+
+// state of a game in progress
+#[derive(Debug, PartialEq)]
+struct Game {
+    starting: Vec<usize>,
+    cups_list: Vec<usize>,
+    current_cup: usize,
+    moves: u64,
+}
+
+impl Game {
+    fn new(cups: &[usize], num_cups: usize) -> Result<Self, String> {
+        Ok(Game {
+            starting: cups.to_vec(),
+            cups_list: build_cups_list(cups, num_cups)?,
+            current_cup: cups[0],
+            moves: 0,
+        })
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        Checkpoint {
+            tag: CHECKPOINT_TAG.to_string(),
+            sections: vec![
+                self.starting.iter().map(|&c| c as u64).collect(),
+                self.cups_list.iter().map(|&c| c as u64).collect(),
+                vec![self.current_cup as u64, self.moves],
+            ],
+        }
+        .save(path)
+    }
+
+    fn load(path: &Path) -> io::Result<Self> {
+        let checkpoint = Checkpoint::load(path, CHECKPOINT_TAG)?;
+        let malformed =
+            || io::Error::new(io::ErrorKind::InvalidData, "malformed day 23 checkpoint");
+        let cup = |c: u64| usize::try_from(c).map_err(|_| malformed());
+
+        let game = match &checkpoint.sections[..] {
+            [starting, cups_list, state] if state.len() == 2 => Game {
+                starting: starting.iter().map(|&c| cup(c)).collect::<Result<_, _>>()?,
+                cups_list: cups_list
+                    .iter()
+                    .map(|&c| cup(c))
+                    .collect::<Result<_, _>>()?,
+                current_cup: cup(state[0])?,
+                moves: state[1],
+            },
+            _ => return Err(malformed()),
+        };
+        if !game.is_valid() {
+            return Err(malformed());
+        }
+        Ok(game)
+    }
+
+    // cups_list links all cups into a single circle and the current cup is one of them
+    fn is_valid(&self) -> bool {
+        let n = self.cups_list.len().saturating_sub(1);
+        if n == 0 || self.cups_list[0] != 0 || !(1..=n).contains(&self.current_cup) {
+            return false;
+        }
+
+        let mut seen = vec![false; n + 1];
+        let mut cup = 1;
+        for _ in 0..n {
+            if !(1..=n).contains(&cup) || seen[cup] {
+                return false;
+            }
+            seen[cup] = true;
+            cup = self.cups_list[cup];
+        }
+        cup == 1
+    }
+}
+
+// Plays `moves` moves with num_cups cups and returns the product of the two cups
+// after cup 1. The state is saved every `every` moves (never for 0) and at the end,
+// and a run picks up from an existing checkpoint of the same game.
+fn run_with_checkpoints(
+    cups: &[usize],
+    num_cups: usize,
+    moves: u64,
+    every: u64,
+    path: &Path,
+) -> io::Result<usize> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
 
-        // build poor man's linked list
-        let mut cups_list: Vec<usize> = vec![0; num_cups + 1];
-        for w in cups.windows(2) {
-            cups_list[w[0]] = w[1];
+    let mut game = if path.exists() {
+        Game::load(path)?
+    } else {
+        Game::new(cups, num_cups).map_err(invalid)?
+    };
+    if game.starting != cups {
+        return Err(invalid("checkpoint has other starting cups".to_string()));
+    }
+    if game.cups_list.len() != num_cups + 1 || game.moves > moves {
+        return Err(invalid(format!(
+            "checkpoint has {} cups after {} moves",
+            game.cups_list.len() - 1,
+            game.moves
+        )));
+    }
+
+    while game.moves < moves {
+        game.current_cup = single_move(&mut game.cups_list, game.current_cup);
+        game.moves += 1;
+        if every > 0 && game.moves % every == 0 {
+            game.save(path)?;
         }
-        cups_list[cups[num_cups - 1]] = cups[0];
+    }
+    game.save(path)?;
+
+    let (next, sec_next) = get_next_two_cups(&game.cups_list, 1);
+    Ok(next * sec_next)
+}
+
+struct Solution;
 
+impl Solution {
+    fn part1(cups: Vec<usize>) -> String {
+        let mut cups_list = build_cups_list(&cups, cups.len()).unwrap();
         let mut current_cup = cups[0];
         for _ in 0..100 {
             current_cup = single_move(&mut cups_list, current_cup);
@@ -70,16 +211,7 @@ impl Solution {
     }
 
     fn part2(cups: Vec<usize>) -> usize {
-        let num_cups = 1_000_000;
-
-        // build poor man's linked list
-        let mut cups_list: Vec<usize> = (1..=(num_cups + 1)).collect();
-        cups_list[num_cups] = cups[0];
-        for w in cups.windows(2) {
-            cups_list[w[0]] = w[1];
-        }
-        cups_list[cups[cups.len() - 1]] = cups.len() + 1;
-
+        let mut cups_list = build_cups_list(&cups, 1_000_000).unwrap();
         let mut current_cup = cups[0];
         for _ in 0..10_000_000 {
             current_cup = single_move(&mut cups_list, current_cup);
@@ -103,9 +235,30 @@ fn main() {
     let timer = Instant::now();
     println!(
         "p2: {} (runtime: {:?})",
-        Solution::part2(cups),
+        Solution::part2(cups.clone()),
         timer.elapsed()
     );
+
+    // usage: day23 [--checkpoint <file> --moves <n> [--cups <n>] [--every <moves>]]
+    let args: Vec<String> = env::args().skip(1).collect();
+    let option = |name: &str| -> Option<u64> {
+        let i = args.iter().position(|a| a == name)?;
+        Some(
+            args.get(i + 1)
+                .and_then(|n| n.parse().ok())
+                .unwrap_or_else(|| panic!("invalid value for {}", name)),
+        )
+    };
+    if let Some(i) = args.iter().position(|a| a == "--checkpoint") {
+        let path = Path::new(args.get(i + 1).expect("missing checkpoint file"));
+        let moves = option("--moves").expect("missing --moves");
+        let num_cups = option("--cups").unwrap_or(1_000_000) as usize;
+        let every = option("--every").unwrap_or(1_000_000);
+        match run_with_checkpoints(&cups, num_cups, moves, every, path) {
+            Ok(product) => println!("after {} moves: {}", moves, product),
+            Err(e) => println!("checkpoint: {}", e),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -119,4 +272,55 @@ mod tests {
         assert_eq!(Solution::part1(cups.clone()), String::from("67384529"));
         assert_eq!(Solution::part2(cups), 149245887792);
     }
+
+    #[test]
+    fn test_day23_checkpoints() {
+        let path = std::env::temp_dir().join(format!("aoc2020-day23-{}.ckpt", std::process::id()));
+        let cups = parse("389125467");
+
+        assert_eq!(
+            build_cups_list(&cups, 9).unwrap()[1..],
+            [2, 5, 8, 6, 4, 7, 3, 9, 1]
+        );
+        assert_eq!(build_cups_list(&cups, 11).unwrap()[7..], [10, 9, 1, 11, 3]);
+        assert!(build_cups_list(&cups, 5).is_err());
+
+        // 10 moves in two runs, the labels after cup 1 are 9 and 2 then
+        let mut cups_list = build_cups_list(&cups, 9).unwrap();
+        let mut current_cup = cups[0];
+        for _ in 0..4 {
+            current_cup = single_move(&mut cups_list, current_cup);
+        }
+        let (next, sec_next) = get_next_two_cups(&cups_list, 1);
+        assert_eq!(
+            run_with_checkpoints(&cups, 9, 4, 3, &path).unwrap(),
+            next * sec_next
+        );
+        assert_eq!(run_with_checkpoints(&cups, 9, 10, 3, &path).unwrap(), 9 * 2);
+        assert!(run_with_checkpoints(&cups, 9, 5, 3, &path).is_err());
+        assert!(run_with_checkpoints(&cups, 20, 50, 3, &path).is_err());
+        assert!(run_with_checkpoints(&parse("123456789"), 9, 20, 3, &path).is_err());
+
+        // hand-edited checkpoints are rejected before resuming
+        let mut game = Game::load(&path).unwrap();
+        game.cups_list[2] = 12;
+        game.save(&path).unwrap();
+        assert!(run_with_checkpoints(&cups, 9, 20, 3, &path).is_err());
+        game.cups_list = build_cups_list(&cups, 9).unwrap();
+        game.cups_list.swap(2, 5);
+        game.save(&path).unwrap();
+        assert!(run_with_checkpoints(&cups, 9, 20, 3, &path).is_err());
+        game.cups_list = build_cups_list(&cups, 9).unwrap();
+        game.current_cup = 10;
+        game.save(&path).unwrap();
+        assert!(run_with_checkpoints(&cups, 9, 20, 3, &path).is_err());
+        game.cups_list.truncate(5);
+        game.current_cup = 1;
+        game.save(&path).unwrap();
+        assert!(run_with_checkpoints(&cups, 9, 20, 3, &path).is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(run_with_checkpoints(&cups, 5, 10, 3, &path).is_err());
+        assert!(!path.exists());
+    }
 }
@@ -0,0 +1,161 @@
+pub mod checkpoint {
+    //! Snapshots of long running puzzle loops. A checkpoint file starts with a tag
+    //! naming the puzzle, followed by sections of little-endian numbers. Sections whose
+    //! values fit into 32 bits are stored with 4 bytes per value.
+
+    use std::convert::{TryFrom, TryInto};
+    use std::fs::{self, File};
+    use std::io::{self, BufReader, BufWriter, Read, Write};
+    use std::path::Path;
+
+    const MAGIC: &[u8; 4] = b"AOCK";
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Checkpoint {
+        pub tag: String,
+        pub sections: Vec<Vec<u64>>,
+    }
+
+    fn invalid(msg: String) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, msg)
+    }
+
+    fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+        let mut buf = [0; 8];
+        r.read_exact(&mut buf)?;
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    // A length read from the file, which can't be larger than the file itself
+    fn read_len(r: &mut impl Read, size: u64) -> io::Result<usize> {
+        let len = read_u64(r)?;
+        match usize::try_from(len) {
+            Ok(n) if len <= size => Ok(n),
+            _ => Err(invalid(format!(
+                "length {} exceeds the file size {}",
+                len, size
+            ))),
+        }
+    }
+
+    impl Checkpoint {
+        // Writes to a temporary file first, so an interrupted save keeps the last
+        // complete checkpoint.
+        pub fn save(&self, path: &Path) -> io::Result<()> {
+            let tmp = path.with_extension("tmp");
+            let mut w = BufWriter::new(File::create(&tmp)?);
+
+            w.write_all(MAGIC)?;
+            w.write_all(&(self.tag.len() as u64).to_le_bytes())?;
+            w.write_all(self.tag.as_bytes())?;
+            w.write_all(&(self.sections.len() as u64).to_le_bytes())?;
+            for section in &self.sections {
+                let wide = section.iter().any(|&v| v > u32::MAX as u64);
+                w.write_all(&(section.len() as u64).to_le_bytes())?;
+                w.write_all(&[if wide { 8 } else { 4 }])?;
+                for &v in section {
+                    if wide {
+                        w.write_all(&v.to_le_bytes())?;
+                    } else {
+                        w.write_all(&(v as u32).to_le_bytes())?;
+                    }
+                }
+            }
+            w.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+            fs::rename(tmp, path)
+        }
+
+        // Reads a checkpoint, failing if it was written for another puzzle
+        pub fn load(path: &Path, tag: &str) -> io::Result<Checkpoint> {
+            let file = File::open(path)?;
+            let size = file.metadata()?.len();
+            let mut r = BufReader::new(file);
+
+            let mut magic = [0; 4];
+            r.read_exact(&mut magic)?;
+            if &magic != MAGIC {
+                return Err(invalid(format!("{} is no checkpoint", path.display())));
+            }
+            let mut name = vec![0; read_len(&mut r, size)?];
+            r.read_exact(&mut name)?;
+            if name != tag.as_bytes() {
+                return Err(invalid(format!(
+                    "{} is a checkpoint of {}, not {}",
+                    path.display(),
+                    String::from_utf8_lossy(&name),
+                    tag
+                )));
+            }
+
+            let mut sections = vec![];
+            for _ in 0..read_len(&mut r, size)? {
+                let len = read_len(&mut r, size)?;
+                let mut width = [0; 1];
+                r.read_exact(&mut width)?;
+                let width = width[0] as usize;
+                if width != 4 && width != 8 {
+                    return Err(invalid(format!("invalid value width {}", width)));
+                }
+
+                let mut bytes = match len.checked_mul(width) {
+                    Some(n) if n as u64 <= size => vec![0; n],
+                    _ => {
+                        return Err(invalid(format!(
+                            "section of {} values exceeds the file size {}",
+                            len, size
+                        )))
+                    }
+                };
+                r.read_exact(&mut bytes)?;
+                sections.push(
+                    bytes
+                        .chunks_exact(width)
+                        .map(|b| match width {
+                            4 => u32::from_le_bytes(b.try_into().unwrap()) as u64,
+                            _ => u64::from_le_bytes(b.try_into().unwrap()),
+                        })
+                        .collect(),
+                );
+            }
+
+            Ok(Checkpoint {
+                tag: tag.to_string(),
+                sections,
+            })
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::env;
+
+        #[test]
+        fn test_checkpoint_round_trip() {
+            let path = env::temp_dir().join(format!("aoc2020-{}.ckpt", std::process::id()));
+            let checkpoint = Checkpoint {
+                tag: "day00".to_string(),
+                sections: vec![vec![1, 2, 3], vec![], vec![u64::MAX, 0]],
+            };
+
+            checkpoint.save(&path).unwrap();
+            assert_eq!(Checkpoint::load(&path, "day00").unwrap(), checkpoint);
+            assert!(Checkpoint::load(&path, "day01").is_err());
+
+            // corrupt lengths are rejected instead of being allocated
+            let mut bytes = fs::read(&path).unwrap();
+            bytes[4..12].copy_from_slice(&u64::MAX.to_le_bytes());
+            fs::write(&path, &bytes).unwrap();
+            let err = Checkpoint::load(&path, "day00").unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+            let mut bytes = fs::read(&path).unwrap();
+            bytes[4..12].copy_from_slice(&5u64.to_le_bytes());
+            bytes[25..33].copy_from_slice(&(u64::MAX / 2).to_le_bytes());
+            fs::write(&path, &bytes).unwrap();
+            let err = Checkpoint::load(&path, "day00").unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            fs::remove_file(path).unwrap();
+        }
+    }
+}